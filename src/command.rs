@@ -0,0 +1,10 @@
+pub(crate) mod explain;
+pub(crate) mod export;
+pub(crate) mod import;
+pub(crate) mod indexes;
+pub(crate) mod lookup;
+pub(crate) mod reconcile;
+pub(crate) mod rollback;
+pub(crate) mod schema;
+pub(crate) mod stats;
+pub(crate) mod verify;
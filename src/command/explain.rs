@@ -0,0 +1,42 @@
+use crate::{config::Config, dex, error::Result};
+use clap::Args;
+use log::trace;
+
+#[derive(Args, Debug)]
+pub struct ExplainArgs {
+    #[arg(help = "DEX entry id or word")]
+    entry: String,
+
+    #[arg(long, help = "include editorial comments and diffs as meaning notes")]
+    notes: bool,
+}
+
+pub async fn run(config: &Config, args: &ExplainArgs) -> Result<()> {
+    trace!("command::explain::run(config: &Config, args: &ExplainArgs) -> Result<()>");
+
+    let dex = dex::AsyncDatabase::connect(&config.dex).await?;
+    let entry = match args.entry.parse::<u32>() {
+        Ok(id) => dex.call(move |dex| dex.entry(id)).await?,
+        Err(_) => {
+            let word = args.entry.clone();
+            dex.call(move |dex| dex.find_entry(&word)).await?
+        }
+    };
+    let Some((id, word)) = entry else {
+        println!("{}: no importable DEX entry", args.entry);
+        return Ok(());
+    };
+
+    println!("entry {id}: {word}");
+    let notes = args.notes;
+    let (definition, explanation) = dex
+        .call(move |dex| {
+            dex.include_notes(notes);
+            dex.explain(id, word)
+        })
+        .await?;
+    print!("{explanation}");
+    println!("definition:");
+    println!("{}", serde_json::to_string_pretty(&definition)?);
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::{config::Config, error::Result, kb};
+use clap::Args;
+use futures::TryStreamExt;
+use log::{info, trace};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[arg(
+        long,
+        help = "output file path -- if not specified print documents to console"
+    )]
+    output: Option<PathBuf>,
+}
+
+pub async fn run(config: &Config, args: &ExportArgs) -> Result<()> {
+    trace!("command::export::run(config: &Config, args: &ExportArgs) -> Result<()>");
+
+    let kb = kb::Database::try_new(&config.kb).await?;
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let mut count = 0;
+    let mut definitions = Box::pin(kb.stream_definitions());
+    while let Some(definition) = definitions.try_next().await? {
+        writeln!(writer, "{}", serde_json::to_string(&definition)?)?;
+        count += 1;
+    }
+    writer.flush()?;
+
+    info!("exported {count} definitions");
+    Ok(())
+}
@@ -0,0 +1,495 @@
+use crate::{
+    config::Config,
+    dex::{
+        self, Selection,
+        extractor::{Extractor, Plan},
+        rejection::Report,
+    },
+    error::{AppError, Result},
+    kb::{
+        self, Checkpoint, SyncPoint,
+        writer::{Flush, WriteMode},
+    },
+    shutdown::Shutdown,
+    util::time::DateTime,
+};
+use clap::Args;
+use log::{debug, info, trace, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    #[arg(long, help = "do not insert records into knowledge database")]
+    dry: bool,
+
+    #[arg(
+        long = "word",
+        value_name = "WORD",
+        help = "import only entries with this headword; can be repeated"
+    )]
+    words: Vec<String>,
+
+    #[arg(
+        long,
+        help = "import only entries with id greater than or equal to this one"
+    )]
+    from_id: Option<u32>,
+
+    #[arg(
+        long,
+        help = "import only entries with id less than or equal to this one"
+    )]
+    to_id: Option<u32>,
+
+    #[arg(long, help = "stop after importing this many entries")]
+    limit: Option<usize>,
+
+    #[arg(
+        long,
+        help = "import only entries listed in this file, one id per line; # starts a comment"
+    )]
+    ids_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["words", "from_id", "to_id", "ids_file", "limit", "notes", "mode"],
+        help = "continue the last import run, with its selection, limit, notes and mode, from its saved checkpoint, updated on every bulk write"
+    )]
+    resume: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "resume",
+        help = "import only entries changed in DEX since the start of the last complete run"
+    )]
+    incremental: bool,
+
+    #[arg(
+        long,
+        value_name = "UNIX_TIME",
+        conflicts_with = "resume",
+        help = "import only entries changed in DEX at or after this unix time"
+    )]
+    since: Option<i64>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["resume", "incremental", "since", "words", "from_id", "to_id", "ids_file", "limit"],
+        help = "import all entries into a staging collection, then swap it with the live one if sanity checks pass"
+    )]
+    rebuild: bool,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "rebuild is not swapped in if it has this many percent fewer documents than the live collection"
+    )]
+    max_shrink: u8,
+
+    #[arg(
+        long,
+        help = "include editorial comments and diffs as meaning notes; internal, keep out of public knowledge databases"
+    )]
+    notes: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = WriteMode::Upsert,
+        help = "insert fails for entries already imported; upsert replaces them by entry id"
+    )]
+    mode: WriteMode,
+
+    #[arg(
+        long,
+        default_value_t = 500,
+        help = "number of entries extracted from DEX per batch, at most 1000"
+    )]
+    batch_size: usize,
+
+    #[arg(
+        long,
+        default_value_t = 4,
+        help = "number of DEX connections extracting batches in parallel, at most 98"
+    )]
+    concurrency: usize,
+
+    #[arg(
+        long,
+        default_value = "reports",
+        help = "directory of rejected expressions and examples reports, one JSON lines file and one summary per run"
+    )]
+    report_dir: PathBuf,
+}
+
+// name of the checkpoint document for import runs
+const CHECKPOINT: &str = "import";
+
+// staging entries looked up before a rebuild is swapped in
+const MAX_SAMPLES: usize = 100;
+
+/// Reason the import loop stopped.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Completed,
+    LimitReached,
+    Interrupted,
+}
+
+pub async fn run(config: &Config, args: &ImportArgs, shutdown: &Shutdown) -> Result<()> {
+    trace!(
+        "command::import::run(config: &Config, args: &ImportArgs, shutdown: &Shutdown) -> Result<()>"
+    );
+    let start = Instant::now();
+
+    if args.batch_size == 0 || args.batch_size > dex::Database::MAX_BATCH_SIZE {
+        return Err(AppError::Input(format!(
+            "batch size should be between 1 and {}",
+            dex::Database::MAX_BATCH_SIZE
+        )));
+    }
+    if args.concurrency == 0 || args.concurrency > dex::Database::MAX_CONCURRENCY {
+        return Err(AppError::Input(format!(
+            "concurrency should be between 1 and {}",
+            dex::Database::MAX_CONCURRENCY
+        )));
+    }
+
+    let dex = dex::AsyncDatabase::connect(&config.dex).await?;
+    let kb = kb::Database::try_new(&config.kb).await?;
+
+    let mut checkpoint = if args.resume {
+        let Some(checkpoint) = kb.load_checkpoint(CHECKPOINT).await? else {
+            return Err(AppError::Input(
+                "no import checkpoint to resume from".to_string(),
+            ));
+        };
+        if checkpoint.completed {
+            warn!("run {} already completed", checkpoint.run_id);
+        }
+        info!(
+            "resume run {} after entry {}, {} entries already imported",
+            checkpoint.run_id, checkpoint.entry_id, checkpoint.count
+        );
+        checkpoint
+    } else {
+        let selection = Selection {
+            words: args.words.clone(),
+            ids: match &args.ids_file {
+                Some(path) => read_ids(path)?,
+                None => Vec::new(),
+            },
+            from_id: args.from_id,
+            to_id: args.to_id,
+            modified_since: None,
+        };
+        let modified_since = match (args.since, args.incremental) {
+            (Some(since), _) => Some(since),
+            (None, true) => {
+                let Some(sync) = kb.load_sync().await? else {
+                    return Err(AppError::Input(
+                        "no complete import run yet, run a full import or use --since".to_string(),
+                    ));
+                };
+                info!("changes since run {}", sync.run_id);
+                Some(sync.started_at)
+            }
+            (None, false) => None,
+        };
+        let mut checkpoint = Checkpoint::new(
+            dex.call(|dex| dex.now()).await?,
+            modified_since,
+            args.rebuild,
+            selection,
+            args.limit,
+        );
+        checkpoint.notes = args.notes;
+        checkpoint.mode = args.mode;
+        checkpoint
+    };
+    let mut selection = checkpoint.selection.clone();
+    // entries already sent by the interrupted run count towards its limit
+    let limit = checkpoint
+        .limit
+        .map(|limit| limit.saturating_sub((checkpoint.count + checkpoint.failed) as usize));
+    info!("import run {}", checkpoint.run_id);
+    if checkpoint.rebuild && !checkpoint.complete {
+        return Err(AppError::Input(
+            "rebuild imports all entries, without selection or limit".to_string(),
+        ));
+    }
+    if checkpoint.modified_since.is_some() && checkpoint.mode == WriteMode::Insert {
+        return Err(AppError::Input(
+            "incremental import needs upsert mode".to_string(),
+        ));
+    }
+    selection.modified_since = checkpoint.modified_since;
+    info!("entries selection: {selection:?}, limit: {limit:?}");
+
+    let mut base_id = checkpoint.entry_id;
+    let mut extractor = Extractor::start(
+        &dex,
+        Plan {
+            selection,
+            base_id,
+            limit,
+            batch_size: args.batch_size,
+            concurrency: args.concurrency,
+            notes: checkpoint.notes,
+        },
+    )
+    .await?;
+
+    let staging = checkpoint.rebuild.then(|| kb.staging());
+    let target = staging.as_ref().unwrap_or(&kb);
+    if !args.dry {
+        if staging.is_some() {
+            if !args.resume {
+                target.drop_collection().await?;
+            }
+            target.install_validator().await?;
+            // other indexes are built once all documents are in
+            target.create_entry_index().await?;
+        } else {
+            target.install_validator().await?;
+            target.ensure_indexes().await?;
+        }
+    }
+    info!("importing into {}", target.collection_name());
+    let mut writer = target.writer(checkpoint.mode);
+    let mut report = Report::create(&args.report_dir, &checkpoint.run_id)?;
+    let mut samples = Vec::new();
+    let mut count = 0;
+    let outcome = 'batches: loop {
+        if shutdown.requested() {
+            break Outcome::Interrupted;
+        }
+        let Some(batch) = extractor.next_batch().await? else {
+            if limit.is_some_and(|limit| count >= limit) {
+                break Outcome::LimitReached;
+            }
+            break Outcome::Completed;
+        };
+        // first entry of every batch, spread over the whole id range
+        if staging.is_some()
+            && let Some((id, definition)) = batch.definitions.first()
+        {
+            samples.push((*id, definition.word().to_string()));
+        }
+
+        let mut rejections = batch.rejections.into_iter().peekable();
+        for (id, definition) in batch.definitions {
+            // checked only between entries so that the one in flight is always fully written
+            if shutdown.requested() {
+                break 'batches Outcome::Interrupted;
+            }
+            base_id = id;
+            count += 1;
+            // reported with their entry; after a crash, entries since the last checkpoint are
+            // reported again, and counted once by the summary
+            while let Some(rejection) = rejections.next_if(|rejection| rejection.entry_id == id) {
+                report.write(&rejection)?;
+            }
+            if !args.dry
+                && let Some(flush) = writer.write(&definition).await?
+            {
+                record(&mut checkpoint, &flush);
+                kb.save_checkpoint(CHECKPOINT, &checkpoint).await?;
+            }
+        }
+    };
+    drop(extractor);
+    checkpoint.completed = outcome == Outcome::Completed;
+    if !args.dry {
+        let flush = writer.flush().await?;
+        record(&mut checkpoint, &flush);
+        kb.save_checkpoint(CHECKPOINT, &checkpoint).await?;
+    }
+    let report_path = report.path().to_path_buf();
+    let summary = report.finish(&checkpoint.run_id)?;
+    if !args.dry {
+        if let Some(staging) = &staging
+            && checkpoint.completed
+        {
+            staging.ensure_indexes().await?;
+            check_staging(&kb, staging, &checkpoint, &samples, args.max_shrink).await?;
+            kb.promote(staging).await?;
+            println!("promoted:       {}", staging.collection_name());
+            info!(
+                "{} replaced by {}, previous one kept for rollback",
+                kb.collection_name(),
+                staging.collection_name()
+            );
+        }
+        // only runs through all entries can be the reference of later incremental runs
+        if checkpoint.complete && checkpoint.completed {
+            if checkpoint.failed > 0 {
+                // failed entries would be skipped by next incremental run
+                warn!(
+                    "{} definitions not written, last sync time not updated",
+                    checkpoint.failed
+                );
+            } else {
+                kb.save_sync(&SyncPoint {
+                    run_id: checkpoint.run_id.clone(),
+                    started_at: checkpoint.started_at,
+                    completed_at: DateTime::now().iso8601(),
+                })
+                .await?;
+                info!("last sync time set to {}", checkpoint.started_at);
+            }
+        }
+    }
+
+    println!("run:            {}", checkpoint.run_id);
+    if let Some(since) = checkpoint.modified_since {
+        println!("changed since:  {since}");
+    }
+    println!("outcome:        {outcome:?}");
+    println!("entries:        {count}");
+    println!("run entries:    {}", checkpoint.count);
+    println!("run failures:   {}", checkpoint.failed);
+    println!("last entry id:  {base_id}");
+    println!(
+        "rejections:     {} in {}",
+        summary.total,
+        report_path.display()
+    );
+    for (reason, count) in &summary.by_reason {
+        println!("  {reason}: {count}");
+    }
+    if summary.unreadable > 0 {
+        warn!(
+            "{} unreadable lines in {}",
+            summary.unreadable,
+            report_path.display()
+        );
+    }
+    println!("elapsed:        {:.1?}", start.elapsed());
+
+    if outcome == Outcome::Interrupted {
+        warn!("DEX import interrupted after entry {base_id}, continue with --resume");
+        return Err(AppError::Interrupted);
+    }
+    info!("DEX import successfully ended: {count} entries, outcome {outcome:?}");
+    Ok(())
+}
+
+/// Sanity checks of a completed rebuild before it replaces the live collection.
+async fn check_staging(
+    kb: &kb::Database,
+    staging: &kb::Database,
+    checkpoint: &Checkpoint,
+    samples: &[(u32, String)],
+    max_shrink: u8,
+) -> Result<()> {
+    trace!(
+        "command::import::check_staging(kb: &kb::Database, staging: &kb::Database, checkpoint: &Checkpoint, samples: &[(u32, String)], max_shrink: u8) -> Result<()>"
+    );
+    if checkpoint.failed > 0 {
+        return Err(AppError::Aborted(format!(
+            "{} definitions not written to {}",
+            checkpoint.failed,
+            staging.collection_name()
+        )));
+    }
+
+    let staging_count = staging.count().await?;
+    let live_count = kb.count().await?;
+    println!("staging:        {staging_count} documents, live {live_count}");
+    if shrunk(staging_count, live_count, max_shrink) {
+        return Err(AppError::Aborted(format!(
+            "{} has {staging_count} documents, {} has {live_count}",
+            staging.collection_name(),
+            kb.collection_name()
+        )));
+    }
+
+    let step = samples.len().div_ceil(MAX_SAMPLES).max(1);
+    for (entry_id, word) in samples.iter().step_by(step) {
+        let found = staging.find_by_entry_id(*entry_id).await?;
+        if found.as_ref().map(|definition| definition.word()) != Some(word) {
+            return Err(AppError::Aborted(format!(
+                "entry {entry_id} {word} not found in {}",
+                staging.collection_name()
+            )));
+        }
+    }
+    debug!("{} staging samples found", samples.len().div_ceil(step));
+    Ok(())
+}
+
+/// Whether a rebuild lost more than `max_shrink` percent of the live documents; an empty one
+/// always has.
+fn shrunk(staging_count: u64, live_count: u64, max_shrink: u8) -> bool {
+    staging_count == 0 || staging_count * 100 < live_count * (100 - u64::from(max_shrink))
+}
+
+fn record(checkpoint: &mut Checkpoint, flush: &Flush) {
+    if let Some(entry_id) = flush.last_entry_id {
+        checkpoint.advance(entry_id, flush.written, flush.failures.len() as u64);
+    }
+}
+
+fn read_ids(path: &Path) -> Result<Vec<u32>> {
+    let content = fs::read_to_string(path)?;
+    let mut ids = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let id = line.parse().map_err(|_| {
+            AppError::Input(format!(
+                "{}:{}: invalid entry id {line}",
+                path.display(),
+                index + 1
+            ))
+        })?;
+        ids.push(id);
+    }
+    if ids.is_empty() {
+        return Err(AppError::Input(format!("{}: no entry ids", path.display())));
+    }
+    // selected ids are paged through in order
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrunk() {
+        // first rebuild, no live collection yet
+        assert!(!shrunk(1, 0, 10));
+        assert!(shrunk(0, 0, 10));
+        assert!(!shrunk(90, 100, 10));
+        assert!(shrunk(89, 100, 10));
+        assert!(!shrunk(120, 100, 0));
+        assert!(shrunk(99, 100, 0));
+        assert!(!shrunk(1, 100, 100));
+    }
+
+    #[test]
+    fn test_read_ids() {
+        let path = std::env::temp_dir().join(format!("dex-ids-{}", std::process::id()));
+        fs::write(&path, "# entries\n13\n3 # casă\n\n8\n3\n").unwrap();
+        let ids = read_ids(&path).unwrap();
+        assert_eq!(ids, vec![3, 8, 13]);
+
+        fs::write(&path, "3\ncasă\n").unwrap();
+        let error = read_ids(&path).unwrap_err();
+        assert!(error.to_string().contains(":2: invalid entry id casă"));
+
+        fs::write(&path, "# none\n").unwrap();
+        assert!(read_ids(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{config::Config, error::Result, kb};
+use clap::{Args, Subcommand};
+use log::{info, trace};
+
+#[derive(Args, Debug)]
+pub struct IndexesArgs {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    #[command(about = "list declared and existing indexes")]
+    List,
+
+    #[command(about = "drop all indexes and create the declared ones")]
+    Rebuild,
+}
+
+pub async fn run(config: &Config, args: &IndexesArgs) -> Result<()> {
+    trace!("command::indexes::run(config: &Config, args: &IndexesArgs) -> Result<()>");
+
+    let kb = kb::Database::try_new(&config.kb).await?;
+    if let Action::Rebuild = args.action {
+        kb.rebuild_indexes().await?;
+        info!("{} indexes rebuilt", kb.collection_name());
+    }
+    for (name, state) in kb.indexes().await? {
+        println!("{name}: {state:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use crate::{
+    config::Config,
+    error::Result,
+    kb,
+    model::{self, Definition, Example, Meaning},
+};
+use clap::Args;
+use log::trace;
+use std::fmt::Write;
+
+#[derive(Args, Debug)]
+pub struct LookupArgs {
+    #[arg(help = "word to look up, any inflected form, with or without diacritics")]
+    word: String,
+
+    #[arg(long, help = "match words starting with given text")]
+    prefix: bool,
+
+    #[arg(long, default_value_t = 20, help = "maximum number of prefix matches")]
+    limit: i64,
+
+    #[arg(long, help = "print inflected forms of every lexeme")]
+    paradigm: bool,
+
+    #[arg(
+        long,
+        help = "print definitions as JSON lines, one compact object per definition and none when not found"
+    )]
+    json: bool,
+}
+
+pub async fn run(config: &Config, args: &LookupArgs) -> Result<()> {
+    trace!("command::lookup::run(config: &Config, args: &LookupArgs) -> Result<()>");
+
+    let kb = kb::Database::try_new(&config.kb).await?;
+    let definitions = if args.prefix {
+        kb.find_by_prefix(&args.word, args.limit).await?
+    } else {
+        find_by_any_form(&kb, &args.word).await?
+    };
+    if definitions.is_empty() && !args.json {
+        println!("{}: not found", args.word);
+    }
+    for definition in definitions {
+        if args.json {
+            println!("{}", serde_json::to_string(&definition)?);
+        } else {
+            println!("{}", render(&definition, args.paradigm));
+        }
+    }
+    Ok(())
+}
+
+/// Definitions with the word as headword or having it, or its ASCII folding, among their key
+/// forms; headwords first.
+async fn find_by_any_form(kb: &kb::Database, word: &str) -> Result<Vec<Definition>> {
+    let [form, folded] = model::key_forms(word);
+    let mut definitions = kb.find_by_word(word).await?;
+    let mut forms = vec![form.clone()];
+    if folded != form {
+        forms.push(folded);
+    }
+    for form in &forms {
+        for definition in kb.find_by_form(form).await? {
+            if !definitions
+                .iter()
+                .any(|found| found.entry_id() == definition.entry_id())
+            {
+                definitions.push(definition);
+            }
+        }
+    }
+    definitions.sort_by_key(|definition| {
+        (
+            !model::key_forms(definition.word()).contains(&form),
+            definition.entry_id(),
+        )
+    });
+    Ok(definitions)
+}
+
+fn render(definition: &Definition, paradigm: bool) -> String {
+    let mut text = String::new();
+    let _ = write!(text, "{}", definition.word());
+    if let Some(part_of_speech) = definition.part_of_speech() {
+        let _ = write!(text, ", {part_of_speech}");
+    }
+    let _ = writeln!(text, "  [entry {}]", definition.entry_id());
+
+    render_meanings(&mut text, definition.meanings(), "", 1);
+    if !definition.expressions().is_empty() {
+        let _ = writeln!(text, "  expressions:");
+    }
+    for expression in definition.expressions() {
+        let _ = writeln!(
+            text,
+            "  - {}: {}",
+            expression.phrase(),
+            expression.definition()
+        );
+        render_examples(&mut text, expression.examples());
+    }
+    for etymology in definition.etymologies() {
+        let _ = writeln!(text, "  etymology: {}", etymology.text());
+    }
+    if paradigm {
+        for paradigm in definition.paradigm() {
+            let _ = writeln!(
+                text,
+                "  paradigm {} ({}):",
+                paradigm.lexeme(),
+                paradigm.model()
+            );
+            for form in paradigm.forms() {
+                let _ = writeln!(text, "    {}: {}", form.inflection(), form.form());
+            }
+        }
+    }
+    text
+}
+
+// meanings without a breadcrumb are numbered by position
+fn render_meanings(text: &mut String, meanings: &[Meaning], parent: &str, depth: usize) {
+    for (index, meaning) in meanings.iter().enumerate() {
+        let breadcrumb = match meaning.breadcrumb() {
+            Some(breadcrumb) => breadcrumb.to_string(),
+            None if parent.is_empty() => (index + 1).to_string(),
+            None => format!("{parent}.{}", index + 1),
+        };
+        let indent = "  ".repeat(depth);
+        let _ = writeln!(text, "{indent}{breadcrumb}. {}", meaning.definition());
+        render_examples(text, meaning.examples());
+        for (kind, relations) in meaning.relations().by_kind() {
+            let words: Vec<&str> = relations.iter().map(|relation| relation.word()).collect();
+            let _ = writeln!(text, "       {kind}: {}", words.join(", "));
+        }
+        for note in meaning.notes() {
+            let _ = writeln!(text, "       {}: {}", note.kind(), note.text());
+        }
+        render_meanings(text, meaning.meanings(), &breadcrumb, depth + 1);
+    }
+}
+
+fn render_examples(text: &mut String, examples: &[Example]) {
+    for example in examples {
+        let _ = match example.source() {
+            Some(source) => writeln!(text, "       > {} ({source})", example.text()),
+            None => writeln!(text, "       > {}", example.text()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let definition = Definition::sample();
+
+        assert_eq!(
+            render(&definition, true),
+            "casă, substantiv feminin  [entry 4294967295]\n\
+             \x20 1. Clădire de locuit.\n\
+             \x20      > Și-a luat casă. (Autor)\n\
+             \x20      synonyms: locuință, clădire\n\
+             \x20      comment: Învechit.\n\
+             \x20   1.1. Locuință.\n\
+             \x20 2. Familie.\n\
+             \x20 expressions:\n\
+             \x20 - casă de bani: Seif.\n\
+             \x20      > Banii stau în casa de bani.\n\
+             \x20 etymology: Din lat. casa.\n\
+             \x20 paradigm casă (F1):\n\
+             \x20   Nominativ-Acuzativ, singular, nearticulat: casă\n"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    config::Config,
+    dex,
+    error::{AppError, Result},
+    kb,
+};
+use clap::Args;
+use log::{info, trace};
+use std::collections::HashSet;
+
+#[derive(Args, Debug)]
+pub struct ReconcileArgs {
+    #[arg(long, help = "only report documents that would be removed")]
+    dry: bool,
+
+    #[arg(
+        long,
+        help = "mark documents with deleted_at instead of deleting them; tombstones are hidden from lookup and verify"
+    )]
+    tombstone: bool,
+
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "abort without removing anything if more documents would be removed"
+    )]
+    max_deletions: usize,
+
+    #[arg(
+        long,
+        help = "also delete legacy documents without entry id, left by imports that predate it"
+    )]
+    legacy: bool,
+
+    #[arg(
+        long,
+        default_value_t = 20,
+        help = "maximum number of removed entries to print"
+    )]
+    show: usize,
+}
+
+pub async fn run(config: &Config, args: &ReconcileArgs) -> Result<()> {
+    trace!("command::reconcile::run(config: &Config, args: &ReconcileArgs) -> Result<()>");
+
+    let dex = dex::AsyncDatabase::connect(&config.dex).await?;
+    let kb = kb::Database::try_new(&config.kb).await?;
+
+    // same entries import walks: deleted, merged and unstructured entries are not there
+    let dex_ids: HashSet<u32> = dex
+        .call(|dex| dex.entries())
+        .await?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let stored = kb.entries().await?;
+    let legacy = kb.count_legacy().await?;
+
+    let stale = stale_entries(&dex_ids, &stored, args.tombstone);
+
+    println!("DEX entries: {}", dex_ids.len());
+    println!("KB entries: {}", stored.len());
+    println!("KB documents without entry id: {legacy}");
+    println!("not in DEX: {}", stale.len());
+    for entry in stale.iter().take(args.show) {
+        println!("  {} {}", entry.entry_id, entry.word);
+    }
+
+    check_removals(
+        stale.len(),
+        args.legacy.then_some(legacy),
+        args.max_deletions,
+    )?;
+    if args.dry {
+        return Ok(());
+    }
+    if args.legacy && legacy > 0 {
+        let count = kb.delete_legacy().await?;
+        println!("deleted without entry id: {count}");
+        info!("{count} legacy knowledge database documents deleted");
+    }
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    let entry_ids: Vec<u32> = stale.iter().map(|entry| entry.entry_id).collect();
+    if args.tombstone {
+        let count = kb.tombstone_entries(&entry_ids).await?;
+        println!("tombstoned: {count}");
+        info!("{count} knowledge database documents tombstoned");
+    } else {
+        let count = kb.delete_entries(&entry_ids).await?;
+        println!("deleted: {count}");
+        info!("{count} knowledge database documents deleted");
+    }
+    Ok(())
+}
+
+/// Stored entries no longer in DEX; tombstones are already removed unless they are to be
+/// deleted for good.
+fn stale_entries<'a>(
+    dex_ids: &HashSet<u32>,
+    stored: &'a [kb::StoredEntry],
+    tombstone: bool,
+) -> Vec<&'a kb::StoredEntry> {
+    stored
+        .iter()
+        .filter(|entry| !dex_ids.contains(&entry.entry_id))
+        .filter(|entry| !(tombstone && entry.tombstone))
+        .collect()
+}
+
+/// Abort when more documents would be removed than allowed, legacy ones included when they
+/// are to be deleted.
+fn check_removals(stale: usize, legacy: Option<u64>, max_deletions: usize) -> Result<()> {
+    let removals = stale as u64 + legacy.unwrap_or_default();
+    if removals > max_deletions as u64 {
+        return Err(AppError::Aborted(format!(
+            "{removals} documents to remove, more than {max_deletions} allowed by --max-deletions"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_id: u32, tombstone: bool) -> kb::StoredEntry {
+        kb::StoredEntry {
+            entry_id,
+            word: format!("cuvânt{entry_id}"),
+            tombstone,
+        }
+    }
+
+    #[test]
+    fn test_stale_entries() {
+        let dex_ids = HashSet::from([1, 3]);
+        let stored = [
+            entry(1, false),
+            entry(2, false),
+            entry(3, false),
+            entry(4, true),
+        ];
+        let ids = |stale: Vec<&kb::StoredEntry>| -> Vec<u32> {
+            stale.iter().map(|entry| entry.entry_id).collect()
+        };
+
+        // tombstones are deleted for good
+        assert_eq!(ids(stale_entries(&dex_ids, &stored, false)), vec![2, 4]);
+        // and not tombstoned again
+        assert_eq!(ids(stale_entries(&dex_ids, &stored, true)), vec![2]);
+        assert!(stale_entries(&dex_ids, &stored[..1], false).is_empty());
+    }
+
+    #[test]
+    fn test_check_removals() {
+        assert!(check_removals(0, None, 0).is_ok());
+        assert!(check_removals(100, None, 100).is_ok());
+        assert!(check_removals(101, None, 100).is_err());
+        assert!(check_removals(60, Some(40), 100).is_ok());
+        assert!(check_removals(60, Some(41), 100).is_err());
+        assert!(check_removals(0, Some(101), 100).is_err());
+    }
+}
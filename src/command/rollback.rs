@@ -0,0 +1,15 @@
+use crate::{config::Config, error::Result, kb};
+use log::{info, trace};
+
+pub async fn run(config: &Config) -> Result<()> {
+    trace!("command::rollback::run(config: &Config) -> Result<()>");
+
+    let kb = kb::Database::try_new(&config.kb).await?;
+    kb.rollback().await?;
+    println!("{}: {} documents", kb.collection_name(), kb.count().await?);
+    info!(
+        "{} rolled back to previous collection",
+        kb.collection_name()
+    );
+    Ok(())
+}
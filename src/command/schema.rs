@@ -0,0 +1,45 @@
+use crate::{error::Result, kb, model::schema};
+use clap::{Args, ValueEnum};
+use log::trace;
+use mongodb::bson::Bson;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct SchemaArgs {
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Format::Json,
+        help = "json is standard JSON Schema; mongo is the $jsonSchema installed as collection validator"
+    )]
+    format: Format,
+
+    #[arg(
+        long,
+        help = "output file path -- if not specified print schema to console"
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Json,
+    Mongo,
+}
+
+pub fn run(args: &SchemaArgs) -> Result<()> {
+    trace!("command::schema::run(args: &SchemaArgs) -> Result<()>");
+
+    let collection_schema = kb::collection_schema()?;
+    let json = match args.format {
+        Format::Json => schema::to_json_schema(&collection_schema, "Definition"),
+        Format::Mongo => Bson::Document(collection_schema).into_relaxed_extjson(),
+    };
+    let json = format!("{json:#}\n");
+    match &args.output {
+        Some(path) => fs::write(path, json)?,
+        None => print!("{json}"),
+    }
+    Ok(())
+}
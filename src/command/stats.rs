@@ -0,0 +1,20 @@
+use crate::{config::Config, dex, error::Result, kb};
+use log::trace;
+
+pub async fn run(config: &Config) -> Result<()> {
+    trace!("command::stats::run(config: &Config) -> Result<()>");
+
+    let dex = dex::AsyncDatabase::connect(&config.dex).await?;
+    let kb = kb::Database::try_new(&config.kb).await?;
+
+    println!(
+        "DEX importable entries: {}",
+        dex.call(|dex| dex.entries()).await?.len()
+    );
+    println!("KB definitions: {}", kb.count().await?);
+    println!("KB definitions by part of speech:");
+    for (part_of_speech, count) in kb.count_by("part_of_speech").await? {
+        println!("  {:<32} {count}", part_of_speech.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
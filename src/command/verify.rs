@@ -0,0 +1,57 @@
+use crate::{
+    config::Config,
+    dex,
+    error::{AppError, Result},
+    kb,
+};
+use clap::Args;
+use log::trace;
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[arg(
+        long,
+        default_value_t = 20,
+        help = "maximum number of differing words to print"
+    )]
+    show: usize,
+}
+
+pub async fn run(config: &Config, args: &VerifyArgs) -> Result<()> {
+    trace!("command::verify::run(config: &Config, args: &VerifyArgs) -> Result<()>");
+
+    let dex = dex::AsyncDatabase::connect(&config.dex).await?;
+    let kb = kb::Database::try_new(&config.kb).await?;
+
+    let dex_words: HashSet<String> = dex
+        .call(|dex| dex.entries())
+        .await?
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect();
+    let kb_words = kb.words().await?;
+
+    let missing: BTreeSet<&String> = dex_words.difference(&kb_words).collect();
+    let extra: BTreeSet<&String> = kb_words.difference(&dex_words).collect();
+
+    println!("DEX words: {}", dex_words.len());
+    println!("KB words: {}", kb_words.len());
+    println!("missing from KB: {}", missing.len());
+    for word in missing.iter().take(args.show) {
+        println!("  {word}");
+    }
+    println!("not in DEX: {}", extra.len());
+    for word in extra.iter().take(args.show) {
+        println!("  {word}");
+    }
+
+    if !missing.is_empty() || !extra.is_empty() {
+        return Err(AppError::Verify(format!(
+            "{} words missing from KB, {} words not in DEX",
+            missing.len(),
+            extra.len()
+        )));
+    }
+    Ok(())
+}
@@ -1,14 +1,111 @@
-use crate::error::Result;
-use crate::model::{Definition, Example, Expression, Meaning};
+pub(crate) mod explain;
+pub(crate) mod extractor;
+pub(crate) mod rejection;
+pub(crate) mod rules;
+
+use crate::config::DexConfig;
+use crate::error::{AppError, Result};
+use crate::model::{
+    self, Definition, Example, Expression, Meaning, NoteKind, Paradigm, RelationKind,
+};
 use crate::util::strings;
-use deunicode::deunicode;
+use explain::Explanation;
 use log::{debug, info, trace, warn};
 use mysql::prelude::*;
 use mysql::*;
-use regex::Regex;
-use std::collections::HashSet;
+use rejection::{Reason, Rejection};
+use rules::{Class, Fallback, Rules};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::task;
+
+/// Restricts the entries walked by [`Database::next_batch`]; empty criteria select everything.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Selection {
+    pub words: Vec<String>,
+    // sorted, without duplicates
+    pub ids: Vec<u32>,
+    pub from_id: Option<u32>,
+    pub to_id: Option<u32>,
+    // unix time; only entries with anything changed after it
+    pub modified_since: Option<i64>,
+}
+
+impl Selection {
+    /// Whether entries are filtered by headword or id, not only by modification date.
+    pub fn restricted(&self) -> bool {
+        !self.words.is_empty()
+            || !self.ids.is_empty()
+            || self.from_id.is_some()
+            || self.to_id.is_some()
+    }
+
+    /// Next ids after `base_id`, at most `size`; the whole list would not fit in a prepared
+    /// statement.
+    fn ids_page(&self, base_id: u32, size: usize) -> &[u32] {
+        let start = self.ids.partition_point(|id| *id <= base_id);
+        let end = self.ids.len().min(start + size);
+        &self.ids[start..end]
+    }
+
+    /// SQL condition, to be appended to a `WHERE` clause on `entry e`, and its parameters;
+    /// `ids` is the page of selected ids, if selected by id.
+    fn condition(&self, ids: &[u32]) -> (String, Vec<Value>) {
+        let mut condition = String::new();
+        let mut params = Vec::new();
+
+        if !self.words.is_empty() {
+            let words = vec![
+                "e.description=? OR e.description LIKE CONCAT(?, ' %') ESCAPE '!'";
+                self.words.len()
+            ];
+            condition.push_str(&format!(" AND ({})", words.join(" OR ")));
+            for word in &self.words {
+                params.push(Value::from(word));
+                params.push(Value::from(escape_like(word)));
+            }
+        }
+        if !self.ids.is_empty() {
+            condition.push_str(&format!(" AND e.id IN ({})", placeholders(ids.len())));
+            params.extend(ids.iter().map(Value::from));
+        }
+        if let Some(from_id) = self.from_id {
+            condition.push_str(" AND e.id>=?");
+            params.push(Value::from(from_id));
+        }
+        if let Some(to_id) = self.to_id {
+            condition.push_str(" AND e.id<=?");
+            params.push(Value::from(to_id));
+        }
+        if let Some(since) = self.modified_since {
+            // every table a definition is built from; relations link related words. Dates
+            // have one second resolution: entries changed in the second the sync point was
+            // taken are imported again, in case the change came after they were read
+            condition.push_str(
+                " AND (e.modDate>=? \
+                OR EXISTS (SELECT 1 FROM treeentry te2 JOIN tree t2 ON te2.treeId=t2.id \
+                    WHERE te2.entryId=e.id AND t2.modDate>=?) \
+                OR EXISTS (SELECT 1 FROM treeentry te2 JOIN meaning m2 ON te2.treeId=m2.treeId \
+                    WHERE te2.entryId=e.id AND m2.modDate>=?) \
+                OR EXISTS (SELECT 1 FROM treeentry te2 JOIN meaning m2 ON te2.treeId=m2.treeId \
+                    JOIN relation r2 ON m2.id=r2.meaningId \
+                    WHERE te2.entryId=e.id AND r2.modDate>=?) \
+                OR EXISTS (SELECT 1 FROM entrylexeme el2 JOIN lexeme l2 ON el2.lexemeId=l2.id \
+                    WHERE el2.entryId=e.id AND l2.modDate>=?) \
+                OR EXISTS (SELECT 1 FROM entrylexeme el2 \
+                    JOIN inflectedform f2 ON el2.lexemeId=f2.lexemeId \
+                    WHERE el2.entryId=e.id AND f2.modDate>=?))",
+            );
+            params.extend(std::iter::repeat_n(Value::from(since), 6));
+        }
 
-#[derive(Debug)]
+        (condition, params)
+    }
+}
+
+#[derive(Debug, Clone)]
 struct Record {
     id: u32,
     parent_id: u32,
@@ -17,117 +114,378 @@ struct Record {
 }
 
 pub struct Database {
+    pool: Pool,
     connection: PooledConn,
+    // relations of the meanings from current batch, by meaning id: kind, tree id and headword
+    relations: HashMap<u32, Vec<(RelationKind, u32, String)>>,
+    // collected only while explaining an entry
+    explanation: Option<Explanation>,
+    rejections: Vec<Rejection>,
+    rules: Arc<Rules>,
+    // editorial comments and diffs are internal, left out unless asked for
+    notes: bool,
 }
 
 impl Database {
-    pub fn try_new(url: &str) -> Result<Self> {
-        trace!("dex::Database::try_new(url: &str) -> Result<Self>");
-        let pool = Pool::new(url)?;
+    // keeps IN (...) lists well below prepared statement placeholders limit
+    const MAX_PARAMS: usize = 1000;
+    pub const MAX_BATCH_SIZE: usize = Database::MAX_PARAMS;
+    // workers share the default size pool with the main and pager connections, and waiting for
+    // a free connection never times out
+    pub const MAX_CONCURRENCY: usize = PoolConstraints::DEFAULT.max() - 2;
+
+    pub fn try_new(config: &DexConfig) -> Result<Self> {
+        trace!("dex::Database::try_new(config: &DexConfig) -> Result<Self>");
+        let opts = OptsBuilder::new()
+            .ip_or_hostname(Some(&config.host))
+            .tcp_port(config.port)
+            .user(Some(&config.user))
+            .pass(config.password.as_ref().map(|password| password.expose()))
+            .db_name(Some(&config.database));
+        let pool = Pool::new(opts)?;
         let connection = pool.get_conn()?;
+        let rules = Rules::load(config.rules.as_deref())?;
 
-        Ok(Self { connection })
+        Ok(Self {
+            pool,
+            connection,
+            relations: HashMap::new(),
+            explanation: None,
+            rejections: Vec::new(),
+            rules: Arc::new(rules),
+            notes: false,
+        })
     }
 
-    pub fn next_word(&mut self, base_id: u32) -> Option<(u32, String)> {
-        trace!("dex::Database::next_word(&mut self, base_id: u32) -> Option<(u32, String)>");
+    /// New database instance with its own connection from the same pool.
+    pub fn try_clone(&self) -> Result<Self> {
+        trace!("dex::Database::try_clone(&self) -> Result<Self>");
+        Ok(Self {
+            pool: self.pool.clone(),
+            connection: self.pool.get_conn()?,
+            relations: HashMap::new(),
+            explanation: None,
+            rejections: Vec::new(),
+            rules: self.rules.clone(),
+            notes: self.notes,
+        })
+    }
 
-        let query = format!(
-            "SELECT e.id,e.description AS word FROM entry e \
+    /// Next batch of at most `size` importable entries with id greater than `base_id`, ordered
+    /// by id.
+    pub fn next_batch(
+        &mut self,
+        base_id: u32,
+        selection: &Selection,
+        size: usize,
+    ) -> Result<Vec<(u32, String)>> {
+        trace!(
+            "dex::Database::next_batch(&mut self, base_id: u32, selection: &Selection, size: usize) -> Result<Vec<(u32, String)>>"
+        );
+
+        let mut base_id = base_id;
+        loop {
+            let ids = selection.ids_page(base_id, size);
+            if !selection.ids.is_empty() && ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            let (condition, mut params) = selection.condition(ids);
+            let query = format!(
+                "SELECT DISTINCT e.id,e.description FROM entry e \
+            JOIN treeentry te ON e.id=te.entryId \
+            JOIN tree t ON te.treeId=t.id \
+            JOIN meaning m ON t.id=m.treeId \
+            WHERE e.id>? AND e.structuristId<>0{condition} \
+            ORDER BY e.id \
+            LIMIT ?"
+            );
+            params.insert(0, Value::from(base_id));
+            params.push(Value::from(size));
+
+            let rows: Vec<(u32, String)> = self.connection.exec(query, params)?;
+            // none of the page ids is importable, try the next page
+            if rows.is_empty()
+                && let Some(last_id) = ids.last()
+            {
+                base_id = *last_id;
+                continue;
+            }
+            return Ok(rows
+                .into_iter()
+                .filter_map(|(id, description)| Some((id, headword(&description)?)))
+                .collect());
+        }
+    }
+
+    /// Current unix time of DEX server, the reference for modification dates.
+    pub fn now(&mut self) -> Result<i64> {
+        trace!("dex::Database::now(&mut self) -> Result<i64>");
+
+        let now: Option<i64> = self.connection.query_first("SELECT UNIX_TIMESTAMP()")?;
+        now.ok_or(AppError::Fatal("DEX server time"))
+    }
+
+    /// Importable entry with given id, if any.
+    pub fn entry(&mut self, entry_id: u32) -> Result<Option<(u32, String)>> {
+        trace!("dex::Database::entry(&mut self, entry_id: u32) -> Result<Option<(u32, String)>>");
+
+        let row: Option<(u32, String)> = self.connection.exec_first(
+            "SELECT e.id,e.description FROM entry e \
         JOIN treeentry te ON e.id=te.entryId \
         JOIN tree t ON te.treeId=t.id \
         JOIN meaning m ON t.id=m.treeId \
-        WHERE e.id>{base_id} AND e.structuristId<>0 \
+        WHERE e.id=? AND e.structuristId<>0 \
+        LIMIT 1",
+            (entry_id,),
+        )?;
+        Ok(row.and_then(|(id, description)| Some((id, headword(&description)?))))
+    }
+
+    /// First importable entry whose headword is the given word, if any.
+    pub fn find_entry(&mut self, word: &str) -> Result<Option<(u32, String)>> {
+        trace!("dex::Database::find_entry(&mut self, word: &str) -> Result<Option<(u32, String)>>");
+
+        let row: Option<(u32, String)> = self.connection.exec_first(
+            "SELECT e.id,e.description FROM entry e \
+        JOIN treeentry te ON e.id=te.entryId \
+        JOIN tree t ON te.treeId=t.id \
+        JOIN meaning m ON t.id=m.treeId \
+        WHERE (e.description=? OR e.description LIKE CONCAT(?, ' %')) AND e.structuristId<>0 \
         ORDER BY e.id \
-        LIMIT 1"
-        );
+        LIMIT 1",
+            (word, word),
+        )?;
+        Ok(row.and_then(|(id, description)| Some((id, headword(&description)?))))
+    }
+
+    /// All importable entries, ordered by id, with their headwords.
+    pub fn entries(&mut self) -> Result<Vec<(u32, String)>> {
+        trace!("dex::Database::entries(&mut self) -> Result<Vec<(u32, String)>>");
 
-        let row: Row = self.connection.query_first(&query).ok()??;
-        let id: u32 = row.get("id")?;
-        let word: String = row.get("word")?;
-        let word = word.split_whitespace().next()?.to_string();
-        Some((id, word))
+        let rows: Vec<(u32, String)> = self.connection.query(
+            "SELECT DISTINCT e.id,e.description FROM entry e \
+        JOIN treeentry te ON e.id=te.entryId \
+        JOIN tree t ON te.treeId=t.id \
+        JOIN meaning m ON t.id=m.treeId \
+        WHERE e.structuristId<>0 \
+        ORDER BY e.id",
+        )?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, description)| Some((id, headword(&description)?)))
+            .collect())
     }
 
     pub fn query(&mut self, definition_id: u32, word: String) -> Result<Definition> {
         trace!(
             "dex::Database::query(&mut self, definition_id: u32, word: String) -> Result<Definition>"
         );
-        debug!("definition_id: {definition_id}, word: {word}");
+
+        let (_, definition) = self
+            .query_batch(vec![(definition_id, word)])?
+            .pop()
+            .ok_or(AppError::Fatal("definition query"))?;
+        Ok(definition)
+    }
+
+    /// Attach editorial comments and diffs to their meanings as notes.
+    pub fn include_notes(&mut self, notes: bool) {
+        trace!("dex::Database::include_notes(&mut self, notes: bool)");
+        self.notes = notes;
+    }
+
+    /// Build the definition of one entry, tracing how its records were classified and parsed.
+    pub fn explain(&mut self, entry_id: u32, word: String) -> Result<(Definition, Explanation)> {
+        trace!(
+            "dex::Database::explain(&mut self, entry_id: u32, word: String) -> Result<(Definition, Explanation)>"
+        );
+
+        self.explanation = Some(Explanation::default());
+        let definition = self.query(entry_id, word);
+        let explanation = self.explanation.take().unwrap_or_default();
+        // already in the explanation
+        self.rejections.clear();
+        Ok((definition?, explanation))
+    }
+
+    /// Build definitions for a batch of entries, with a fixed number of queries per batch.
+    ///
+    /// Meanings, inflected forms, parts of speech and relations are loaded for the whole batch
+    /// using `IN (...)` queries, then grouped in memory by entry id. Returned definitions keep
+    /// the order of given entries.
+    pub fn query_batch(&mut self, entries: Vec<(u32, String)>) -> Result<Vec<(u32, Definition)>> {
+        trace!(
+            "dex::Database::query_batch(&mut self, entries: Vec<(u32, String)>) -> Result<Vec<(u32, Definition)>>"
+        );
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+        let entry_ids: Vec<u32> = entries.iter().map(|(id, _)| *id).collect();
+        debug!(
+            "batch of {} entries, ids {}..={}",
+            entry_ids.len(),
+            entry_ids[0],
+            entry_ids[entry_ids.len() - 1]
+        );
 
         let definition_query = format!(
-            "SELECT m.id AS id,m.parentId AS parent_id,m.internalRep AS text,m.type AS kind FROM entry e \
-        JOIN treeentry te ON e.id=te.entryId \
+            "SELECT te.entryId,m.id,m.parentId,m.internalRep,m.type FROM treeentry te \
         JOIN tree t ON te.treeId=t.id \
         JOIN meaning m ON t.id=m.treeId \
-        WHERE e.id={definition_id} AND e.structuristId<>0 \
-        ORDER BY m.displayOrder"
+        WHERE te.entryId IN ({}) \
+        ORDER BY te.entryId,m.treeId,m.displayOrder",
+            placeholders(entry_ids.len())
         );
 
         let inflections_query = format!(
-            "SELECT DISTINCT f.formUtf8General FROM entry e 
-        JOIN entrylexeme el ON e.id=el.entryId \
+            "SELECT DISTINCT el.entryId,f.formUtf8General FROM entrylexeme el \
         JOIN inflectedform f ON el.lexemeId=f.lexemeId \
-        WHERE e.id={definition_id} and e.structuristId<>0"
+        WHERE el.entryId IN ({})",
+            placeholders(entry_ids.len())
         );
 
+        // the first inflection of main lexeme describes the part of speech
         let part_of_speech_query = format!(
-            "SELECT i.description FROM entrylexeme el 
+            "SELECT x.entryId,i.description FROM \
+        (SELECT el.entryId,MIN(_if.inflectionId) AS inflectionId FROM entrylexeme el \
         JOIN inflectedform _if ON el.lexemeId=_if.lexemeId \
-        JOIN inflection i ON _if.inflectionId=i.id \
-        WHERE el.entryId={definition_id} AND el.main=1"
+        WHERE el.entryId IN ({}) AND el.main=1 \
+        GROUP BY el.entryId) x \
+        JOIN inflection i ON x.inflectionId=i.id",
+            placeholders(entry_ids.len())
+        );
+
+        // forms of every lexeme, in inflection order
+        let paradigm_query = format!(
+            "SELECT el.entryId,l.id,l.formNoAccent,CONCAT(l.modelType,l.modelNumber),\
+        i.description,f.formNoAccent FROM entrylexeme el \
+        JOIN lexeme l ON el.lexemeId=l.id \
+        JOIN inflectedform f ON l.id=f.lexemeId \
+        JOIN inflection i ON f.inflectionId=i.id \
+        WHERE el.entryId IN ({}) \
+        ORDER BY el.entryId,el.main DESC,l.id,i.rank,f.variant",
+            placeholders(entry_ids.len())
         );
 
-        let records: Vec<Record> =
-            self.connection
-                .query_map(definition_query, |(id, parent_id, text, kind)| Record {
-                    id,
-                    parent_id,
-                    text,
-                    kind,
-                })?;
+        let rows: Vec<(u32, u32, u32, String, u8)> =
+            self.connection.exec(definition_query, &entry_ids)?;
+        let mut records = group_by_entry(rows.into_iter().map(
+            |(entry_id, id, parent_id, text, kind)| {
+                (
+                    entry_id,
+                    Record {
+                        id,
+                        parent_id,
+                        text,
+                        kind,
+                    },
+                )
+            },
+        ));
+
+        let rows: Vec<(u32, String)> = self.connection.exec(inflections_query, &entry_ids)?;
+        let mut inflections = group_by_entry(rows);
 
-        let inflections: Vec<String> = self
-            .connection
-            .query_map(inflections_query, |inflection| inflection)?;
+        let rows: Vec<(u32, String)> = self.connection.exec(part_of_speech_query, &entry_ids)?;
+        let mut parts_of_speech: HashMap<u32, String> = rows.into_iter().collect();
 
-        let part_of_speech: Option<String> = self.connection.query_first(part_of_speech_query)?;
+        let rows: Vec<(u32, u32, String, String, String, String)> =
+            self.connection.exec(paradigm_query, &entry_ids)?;
+        let mut paradigms = group_paradigms(rows);
 
-        self.records_to_definition(word, part_of_speech, inflections, records)
+        let meaning_ids: Vec<u32> = records.values().flatten().map(|r| r.id).collect();
+        self.load_relations(&meaning_ids)?;
+
+        let mut definitions = Vec::with_capacity(entries.len());
+        for (entry_id, word) in entries {
+            debug!("definition_id: {entry_id}, word: {word}");
+            let definition = self.records_to_definition(
+                entry_id,
+                word,
+                parts_of_speech.remove(&entry_id),
+                inflections.remove(&entry_id).unwrap_or_default(),
+                paradigms.remove(&entry_id).unwrap_or_default(),
+                records.remove(&entry_id).unwrap_or_default(),
+            )?;
+            definitions.push((entry_id, definition));
+        }
+        Ok(definitions)
+    }
+
+    /// Replace relations cache with the relations of given meanings.
+    fn load_relations(&mut self, meaning_ids: &[u32]) -> Result<()> {
+        trace!("dex::Database::load_relations(&mut self, meaning_ids: &[u32]) -> Result<()>");
+
+        self.relations.clear();
+        for chunk in meaning_ids.chunks(Database::MAX_PARAMS) {
+            let query = format!(
+                "SELECT r.meaningId,r.type,r.treeId,t.description FROM relation r \
+            JOIN tree t ON r.treeId=t.id \
+            WHERE r.meaningId IN ({}) \
+            ORDER BY r.id",
+                placeholders(chunk.len())
+            );
+            let rows: Vec<(u32, u8, u32, String)> = self.connection.exec(query, chunk.to_vec())?;
+            // every meaning is in a single chunk
+            self.relations.extend(group_relations(rows));
+        }
+        Ok(())
     }
 
     fn records_to_definition(
         &mut self,
+        entry_id: u32,
         word: String,
         part_of_speech: Option<String>,
         inflections: Vec<String>,
+        paradigms: Vec<Paradigm>,
         records: Vec<Record>,
     ) -> Result<Definition> {
         trace!(
-            "dex::Database::records_to_definition(&mut self, word: String, inflections: Vec<String>, records: Vec<Record>,) -> Result<Definition>"
+            "dex::Database::records_to_definition(&mut self, entry_id: u32, word: String, inflections: Vec<String>, paradigms: Vec<Paradigm>, records: Vec<Record>,) -> Result<Definition>"
         );
 
+        let part_of_speech = part_of_speech.map(|raw| {
+            let parsed = self.parse_part_of_speech(&raw);
+            (raw, parsed)
+        });
+        self.explain_with(|explanation| {
+            explanation.source(&records, &inflections, part_of_speech.clone())
+        });
+
         let mut keys = HashSet::new();
         for inflection in inflections {
-            let key = inflection.to_lowercase();
-            keys.insert(deunicode(&key));
-            keys.insert(key);
+            keys.extend(model::key_forms(&inflection));
         }
 
-        let mut definition_builder = Definition::builder().word(&word);
+        let mut definition_builder = Definition::builder().entry_id(entry_id).word(&word);
         for key in keys {
             definition_builder = definition_builder.key(&key);
         }
-        if let Some(part_of_speech) = part_of_speech {
-            definition_builder =
-                definition_builder.part_of_speech(&self.parse_part_of_speech(&part_of_speech));
+        if let Some((_, part_of_speech)) = part_of_speech {
+            definition_builder = definition_builder.part_of_speech(&part_of_speech);
+        }
+        for paradigm in paradigms {
+            definition_builder = definition_builder.paradigm(paradigm);
         }
 
-        let r_missing_definition = Regex::new(r"^(\(.+\)|.+:)$")?;
-        let r_incomplete_meaning = Regex::new(r"^\$\((.+)\)\$\s*$")?;
+        for record in records.iter().filter(|r| r.kind == 1 && !r.text.is_empty()) {
+            let etymology = self.rules.etymology(&record.text);
+            self.explain_with(|explanation| explanation.etymology(record.id, &etymology));
+            definition_builder = definition_builder.etymology(etymology);
+        }
+
+        // kept meanings with their parent meaning id, nested once all are built
+        let mut meanings: Vec<(u32, u32, Meaning)> = Vec::new();
+        let mut parents: HashMap<u32, u32> = HashMap::new();
         for item in DefIterator::new(records) {
-            let mut definition_type = match item.definition.as_str() {
-                "" => {
+            parents.insert(item.id, item.parent_id);
+            let class = self.rules.classify(&item.definition);
+            self.explain_with(|explanation| explanation.item(&item, class));
+
+            let s = item.definition.as_str();
+            let mut definition_type = match class {
+                Class::Empty => {
                     let Some(synonymous) = self.synonymous(item.id) else {
                         continue;
                     };
@@ -135,7 +493,7 @@ impl Database {
                     DefType::Meaning(Meaning::new(&definition))
                 }
 
-                s if r_incomplete_meaning.is_match(s) => {
+                Class::IncompleteMeaning => {
                     let Some(synonymous) = self.synonymous(item.id) else {
                         continue;
                     };
@@ -147,7 +505,7 @@ impl Database {
                     DefType::Meaning(Meaning::new(&definition))
                 }
 
-                s if r_missing_definition.is_match(s) => {
+                Class::MissingDefinition => {
                     let Some(synonymous) = self.synonymous(item.id) else {
                         continue;
                     };
@@ -163,9 +521,13 @@ impl Database {
                     DefType::Meaning(Meaning::new(&definition))
                 }
 
-                s if s.starts_with("$") => {
-                    let Some((phrase, definition)) = self.parse_expression(item.id, s) else {
-                        continue;
+                Class::Expression => {
+                    let (phrase, definition) = match self.parse_expression(item.id, s) {
+                        Ok(expression) => expression,
+                        Err(reason) => {
+                            self.reject(entry_id, item.id, s, reason);
+                            continue;
+                        }
                     };
                     DefType::Expression(Expression::new(
                         &self.normalize_text(&phrase),
@@ -173,18 +535,29 @@ impl Database {
                     ))
                 }
 
-                _ => DefType::Meaning(Meaning::new(&self.normalize_text(&item.definition))),
+                Class::Plain => DefType::Meaning(Meaning::new(&self.normalize_text(s))),
             };
+            self.explain_with(|explanation| explanation.outcome(definition_type.describe()));
 
-            for example in item.examples {
-                if let Some(example) = self.parse_example(&example) {
-                    definition_type.add_example(example);
+            for example in &item.examples {
+                match self.parse_example(example) {
+                    Ok(example) => definition_type.add_example(example),
+                    Err(reason) => self.reject(entry_id, item.id, example, reason),
                 }
             }
 
             match definition_type {
-                DefType::Meaning(meaning) => {
-                    definition_builder = definition_builder.meaning(meaning);
+                DefType::Meaning(mut meaning) => {
+                    for (kind, tree_id, word) in self.relations.get(&item.id).into_iter().flatten()
+                    {
+                        meaning.add_relation(*kind, *tree_id, word);
+                    }
+                    if self.notes {
+                        for (kind, text) in &item.notes {
+                            meaning.add_note(*kind, &self.normalize_text(text));
+                        }
+                    }
+                    meanings.push((item.id, item.parent_id, meaning));
                 }
                 DefType::Expression(expression) => {
                     definition_builder = definition_builder.expression(expression);
@@ -192,9 +565,34 @@ impl Database {
             }
         }
 
+        for meaning in nest_meanings(meanings, &parents) {
+            definition_builder = definition_builder.meaning(meaning);
+        }
         definition_builder.build()
     }
 
+    fn reject(&mut self, entry_id: u32, meaning_id: u32, text: &str, reason: Reason) {
+        warn!("rejected text of entry {entry_id}, meaning {meaning_id}, {reason}: {text}");
+        self.explain_with(|explanation| explanation.rejected(text, reason));
+        self.rejections.push(Rejection {
+            entry_id,
+            meaning_id,
+            text: text.to_string(),
+            reason,
+        });
+    }
+
+    /// Texts rejected since last call.
+    pub fn take_rejections(&mut self) -> Vec<Rejection> {
+        std::mem::take(&mut self.rejections)
+    }
+
+    fn explain_with(&mut self, f: impl FnOnce(&mut Explanation)) {
+        if let Some(explanation) = self.explanation.as_mut() {
+            f(explanation);
+        }
+    }
+
     fn parse_part_of_speech(&self, part_of_speech: &str) -> String {
         match part_of_speech {
             s if s.starts_with("Adjectiv, masculin") => "Adjectiv masculin",
@@ -202,134 +600,307 @@ impl Database {
             s if s.starts_with("Pronume, masculin") => "Pronume masculin",
             s if s.starts_with("Pronume, feminin") => "Pronume feminin",
             "Invariabil" | "Formă unică" => part_of_speech,
-            _ => match part_of_speech.split(",").into_iter().next() {
-                Some(s) => s,
-                None => "", // just to make compiler happy
-            },
+            _ => part_of_speech.split(",").next().unwrap_or_default(),
         }
         .to_string()
     }
 
-    fn parse_expression(&mut self, record_id: u32, expression: &str) -> Option<(String, String)> {
+    fn parse_expression(
+        &mut self,
+        record_id: u32,
+        expression: &str,
+    ) -> std::result::Result<(String, String), Reason> {
         trace!(
-            "dex::Database::parse_expression(&mut self, record_id: u32, expression: &str) -> Option<(String, String)>"
+            "dex::Database::parse_expression(&mut self, record_id: u32, expression: &str) -> std::result::Result<(String, String), Reason>"
         );
 
         let expression = expression.strip_suffix('.').unwrap_or(expression);
 
-        let r = Regex::new(r"^\$([^=]+) =\$?(?: (.+))?$").ok()?;
-        if let Some(captures) = r.captures(expression) {
-            let phrase = captures.get(1).map(|m| m.as_str().to_string())?;
-            let definition = match captures.get(2) {
-                Some(definition) => definition.as_str().to_string(),
-                None => format!("(sinonim) {}", self.synonymous(record_id)?),
+        // rules are shared, lookups below need `self` mutably
+        let rules = self.rules.clone();
+        for rule in rules.expressions() {
+            let Some(captures) = rule.pattern.captures(expression) else {
+                continue;
             };
-            return Some((phrase, definition));
-        }
-
-        let r = Regex::new(r"^\$(.+)\$ (.+)$").ok()?;
-        if let Some(captures) = r.captures(expression) {
-            let phrase = captures.get(1).map(|m| m.as_str().to_string())?;
-            let definition = captures.get(2).map(|m| m.as_str().to_string())?;
-            return Some((phrase, definition));
-        }
+            let phrase = rule
+                .phrase
+                .and_then(|group| captures.get(group))
+                .map_or(expression, |phrase| phrase.as_str())
+                .to_string();
+            if let Some(definition) = rule.definition.and_then(|group| captures.get(group)) {
+                return Ok((phrase, definition.as_str().to_string()));
+            }
 
-        let r = Regex::new(r"\[(\d+)\*?\]").ok()?;
-        if let Some(definition_id) = r
-            .captures(expression)
-            .and_then(|cap| cap.get(1))
-            .and_then(|id| id.as_str().parse::<u32>().ok())
-        {
-            let definition_query =
-                format!("SELECT internalRep FROM meaning WHERE id={definition_id}");
-            let definition: Option<String> = self.connection.query_first(definition_query).ok()?;
-            if let Some(mut definition) = definition {
-                let r_definition = Regex::new(r"^[\$\(][^=]+ =(?: (.+?)\.?)?$").ok()?;
-                if let Some(captures) = r_definition.captures(&definition) {
-                    definition = captures.get(1).map(|m| m.as_str())?.to_string();
-                    return Some((expression.to_string(), definition));
-                } else {
-                    return Some((expression.to_string(), definition));
+            match rule.fallback {
+                Some(Fallback::Synonyms) => {
+                    let synonymous = self
+                        .synonymous(record_id)
+                        .ok_or(Reason::ExpressionWithoutSynonyms)?;
+                    return Ok((phrase, format!("(sinonim) {synonymous}")));
                 }
-            };
-        };
-
-        let r = Regex::new(r"^\$(.+?)\$\.?$").ok()?;
-        if let Some(captures) = r.captures(expression) {
-            let phrase = captures.get(1).map(|m| m.as_str().to_string())?;
-            let query = format!(
-                "SELECT m.internalRep AS definition FROM relation r \
-                JOIN meaning m ON r.treeId=m.treeId \
-                WHERE r.meaningId={record_id} AND m.`type` IN (0,5)"
-            );
-            let definition: Option<String> = self.connection.query_first(query).ok()?;
-            if let Some(definition) = definition {
-                return Some((phrase, self.normalize_text(&definition)));
-            } else {
-                info!("missing definition for related meaning for expression wiht id {record_id}");
+                Some(Fallback::ReferencedMeaning) => {
+                    let Some(definition_id) = rule
+                        .reference
+                        .and_then(|group| captures.get(group))
+                        .and_then(|id| id.as_str().parse::<u32>().ok())
+                    else {
+                        continue;
+                    };
+                    let definition: Option<String> = self
+                        .connection
+                        .exec_first(
+                            "SELECT internalRep FROM meaning WHERE id=?",
+                            (definition_id,),
+                        )
+                        .map_err(|_| Reason::LookupFailed)?;
+                    self.explain_with(|explanation| {
+                        explanation.note(format!(
+                            "referenced meaning {definition_id}: {}",
+                            definition.as_deref().unwrap_or("missing")
+                        ))
+                    });
+                    if let Some(definition) = definition {
+                        return Ok((phrase, rules.referenced_definition(definition)?));
+                    }
+                }
+                Some(Fallback::RelatedMeaning) => {
+                    let query = "SELECT m.internalRep AS definition FROM relation r \
+                        JOIN meaning m ON r.treeId=m.treeId \
+                        WHERE r.meaningId=? AND m.`type` IN (0,5)";
+                    let definition: Option<String> = self
+                        .connection
+                        .exec_first(query, (record_id,))
+                        .map_err(|_| Reason::LookupFailed)?;
+                    self.explain_with(|explanation| {
+                        explanation.note(format!(
+                            "related meaning of {record_id}: {}",
+                            definition.as_deref().unwrap_or("missing")
+                        ))
+                    });
+                    match definition {
+                        Some(definition) => {
+                            return Ok((phrase, self.normalize_text(&definition)));
+                        }
+                        None => info!(
+                            "missing definition for related meaning for expression wiht id {record_id}"
+                        ),
+                    }
+                }
+                None => {}
             }
         }
 
-        warn!("rejected expression: record_id: {record_id}, expression: {expression}");
-        None
+        Err(Reason::UnrecognizedExpression)
     }
 
-    fn parse_example(&self, example: &str) -> Option<Example> {
-        trace!("dex::Database::parse_example(&self, example: &str) -> Option<Example>");
-
-        let r = Regex::new(r"^(.+)\$\.?(?:\s\(?(.+?)\)?)?\.?$").ok()?;
-        if let Some(captures) = r.captures(example) {
-            let text = captures.get(1).map(|m| m.as_str())?;
-            let mut example = Example::new(&self.normalize_text(text));
-            if let Some(source) = captures.get(2).map(|m| m.as_str()) {
-                example.set_source(source);
-            }
-            return Some(example);
-        }
+    fn parse_example(&self, example: &str) -> std::result::Result<Example, Reason> {
+        trace!(
+            "dex::Database::parse_example(&self, example: &str) -> std::result::Result<Example, Reason>"
+        );
 
-        let r = Regex::new(r"^\$([^=]+) =\$?(?: (.+))?$").ok()?;
-        if r.is_match(example) {
-            warn!("expression used as example: {example}");
-        }
-        warn!("rejected example: {example}");
-        None
+        self.rules.example(example)
     }
 
     fn synonymous(&mut self, meaning_id: u32) -> Option<String> {
         trace!("dex::Database::synonymous(&mut self, meaning_id: u32) -> Option<String>");
 
-        let query = format!(
-            "SELECT t.description FROM relation r JOIN tree t ON r.treeId=t.id WHERE r.type=1 AND r.meaningId={meaning_id}"
-        );
-        let synonymous: Vec<String> = self.connection.query(query).ok()?;
-        let filtered: Vec<&str> = synonymous.iter().map(|s| strings::first_word(s)).collect();
-        if filtered.is_empty() {
-            None
-        } else {
-            Some(filtered.join(", "))
-        }
+        let synonymous = self
+            .relations
+            .get(&meaning_id)
+            .map(|relations| {
+                relations
+                    .iter()
+                    .filter(|(kind, _, _)| *kind == RelationKind::Synonym)
+                    .map(|(_, _, word)| word.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            })
+            .filter(|synonymous| !synonymous.is_empty());
+        self.explain_with(|explanation| {
+            explanation.note(match &synonymous {
+                Some(synonymous) => format!("synonyms of meaning {meaning_id}: {synonymous}"),
+                None => format!("no synonyms for meaning {meaning_id}"),
+            })
+        });
+        synonymous
     }
 
     fn normalize_text(&self, text: &str) -> String {
-        let r = Regex::new(r"\[(\d+).*?\]").unwrap();
-        let text = r.replace_all(text, "").to_string();
+        self.rules.normalize(text)
+    }
+}
 
-        let r = Regex::new(r"\u{0022}(.*?)\u{0022}").unwrap();
-        let text = r.replace_all(&text, "\u{2018}$1\u{2019}");
+/// Async access to DEX database.
+///
+/// MySQL driver is blocking; every call runs on tokio blocking threads so that async commands
+/// never stall the runtime while waiting for DEX queries.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    database: Arc<Mutex<Database>>,
+}
 
-        let r = Regex::new(r"\u{00AB}(.*?)\u{00BB}").unwrap();
-        let text = r.replace_all(&text, "\u{2018}$1\u{2019}");
+impl AsyncDatabase {
+    pub async fn connect(config: &DexConfig) -> Result<Self> {
+        trace!("dex::AsyncDatabase::connect(config: &DexConfig) -> Result<Self>");
+        let config = config.clone();
+        let database = task::spawn_blocking(move || Database::try_new(&config)).await??;
+        Ok(Self {
+            database: Arc::new(Mutex::new(database)),
+        })
+    }
 
-        return text.replace("$", "");
+    /// Run given closure against the blocking database on a blocking thread.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let database = self.database.clone();
+        task::spawn_blocking(move || {
+            let mut database = database
+                .lock()
+                .map_err(|_| AppError::Fatal("DEX database lock"))?;
+            f(&mut database)
+        })
+        .await?
     }
 }
 
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+/// Word matched literally by `LIKE ... ESCAPE '!'`, with its wildcards and the escape
+/// character escaped; backslash is not the escape character in every SQL mode.
+fn escape_like(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if matches!(c, '!' | '%' | '_') {
+            escaped.push('!');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Rows of a batch query grouped by their entry id, in row order within every entry.
+fn group_by_entry<T>(rows: impl IntoIterator<Item = (u32, T)>) -> HashMap<u32, Vec<T>> {
+    let mut groups: HashMap<u32, Vec<T>> = HashMap::new();
+    for (entry_id, row) in rows {
+        groups.entry(entry_id).or_default().push(row);
+    }
+    groups
+}
+
+/// Relation rows `(meaning id, type, tree id, tree description)` grouped by meaning id, as
+/// kind, tree id and headword; unknown types are skipped.
+fn group_relations(
+    rows: Vec<(u32, u8, u32, String)>,
+) -> HashMap<u32, Vec<(RelationKind, u32, String)>> {
+    let mut relations: HashMap<u32, Vec<(RelationKind, u32, String)>> = HashMap::new();
+    for (meaning_id, kind, tree_id, description) in rows {
+        let Some(kind) = RelationKind::from_type(kind) else {
+            debug!("unknown relation type {kind} of meaning {meaning_id}");
+            continue;
+        };
+        relations.entry(meaning_id).or_default().push((
+            kind,
+            tree_id,
+            strings::first_word(&description).to_string(),
+        ));
+    }
+    relations
+}
+
+/// Paradigm rows `(entry id, lexeme id, lexeme, model, inflection, form)`, ordered by entry
+/// and lexeme, grouped into one paradigm per lexeme of every entry.
+fn group_paradigms(
+    rows: Vec<(u32, u32, String, String, String, String)>,
+) -> HashMap<u32, Vec<Paradigm>> {
+    let mut paradigms: HashMap<u32, Vec<Paradigm>> = HashMap::new();
+    let mut last_lexeme = None;
+    for (entry_id, lexeme_id, lexeme, model, inflection, form) in rows {
+        let paradigms = paradigms.entry(entry_id).or_default();
+        if last_lexeme != Some((entry_id, lexeme_id)) {
+            paradigms.push(Paradigm::new(&lexeme, &model));
+            last_lexeme = Some((entry_id, lexeme_id));
+        }
+        if let Some(paradigm) = paradigms.last_mut() {
+            paradigm.add_form(&inflection, &form);
+        }
+    }
+    paradigms
+}
+
+/// Entry description may contain disambiguation text after the word, e.g. `bancă (instituție)`.
+fn headword(description: &str) -> Option<String> {
+    description.split_whitespace().next().map(str::to_string)
+}
+
+/// Meaning trees from `(id, parent id, meaning)` items; sub-meanings of a skipped meaning are
+/// attached to its nearest kept ancestor. Meanings are numbered once nested, e.g. `1`, `1.1`.
+fn nest_meanings(
+    mut meanings: Vec<(u32, u32, Meaning)>,
+    parents: &HashMap<u32, u32>,
+) -> Vec<Meaning> {
+    let kept: HashSet<u32> = meanings.iter().map(|(id, _, _)| *id).collect();
+    for (_, parent_id, _) in &mut meanings {
+        for _ in 0..parents.len() {
+            if *parent_id == 0 || kept.contains(parent_id) {
+                break;
+            }
+            *parent_id = parents.get(parent_id).copied().unwrap_or_default();
+        }
+        if !kept.contains(parent_id) {
+            *parent_id = 0;
+        }
+    }
+    take_children(0, "", &mut meanings)
+}
+
+fn take_children(
+    parent_id: u32,
+    parent_breadcrumb: &str,
+    meanings: &mut Vec<(u32, u32, Meaning)>,
+) -> Vec<Meaning> {
+    let (children, rest): (Vec<_>, Vec<_>) = meanings
+        .drain(..)
+        .partition(|(_, parent, _)| *parent == parent_id);
+    *meanings = rest;
+    children
+        .into_iter()
+        .enumerate()
+        .map(|(index, (id, _, mut meaning))| {
+            let breadcrumb = match parent_breadcrumb {
+                "" => (index + 1).to_string(),
+                parent => format!("{parent}.{}", index + 1),
+            };
+            meaning.set_breadcrumb(&breadcrumb);
+            for sub_meaning in take_children(id, &breadcrumb, meanings) {
+                meaning.add_meaning(sub_meaning);
+            }
+            meaning
+        })
+        .collect()
+}
+
 enum DefType {
     Meaning(Meaning),
     Expression(Expression),
 }
 
 impl DefType {
+    fn describe(&self) -> String {
+        match self {
+            DefType::Meaning(meaning) => format!("meaning: {}", meaning.definition()),
+            DefType::Expression(expression) => format!(
+                "expression: {} = {}",
+                expression.phrase(),
+                expression.definition()
+            ),
+        }
+    }
+
     fn add_example(&mut self, example: Example) {
         match self {
             DefType::Meaning(meaning) => meaning.add_example(example),
@@ -340,20 +911,45 @@ impl DefType {
 
 struct DefItem {
     id: u32,
+    // nearest ancestor meaning, 0 for top level items
+    parent_id: u32,
     definition: String,
     examples: Vec<String>,
+    notes: Vec<(NoteKind, String)>,
 }
 
 struct DefIterator {
     records: Vec<Record>,
     definition_id: usize,
+    parents: HashMap<u32, u32>,
 }
 
 impl DefIterator {
     fn new(records: Vec<Record>) -> Self {
+        let kinds: HashMap<u32, (u32, u8)> = records
+            .iter()
+            .map(|r| (r.id, (r.parent_id, r.kind)))
+            .collect();
+        let mut parents = HashMap::new();
+        for record in records.iter().filter(|r| matches!(r.kind, 0 | 5)) {
+            // bounded walk, in case of a malformed tree
+            let mut parent_id = record.parent_id;
+            for _ in 0..records.len() {
+                match kinds.get(&parent_id) {
+                    Some((_, 0)) | None => break,
+                    Some((grandparent_id, _)) => parent_id = *grandparent_id,
+                }
+            }
+            if !matches!(kinds.get(&parent_id), Some((_, 0))) {
+                parent_id = 0;
+            }
+            parents.insert(record.id, parent_id);
+        }
+
         Self {
             records,
             definition_id: 0,
+            parents,
         }
     }
 }
@@ -380,11 +976,205 @@ impl Iterator for DefIterator {
             .filter(|r| r.kind == 2 && r.parent_id == id)
             .map(|r| r.text.clone())
             .collect();
+        let notes: Vec<(NoteKind, String)> = self
+            .records
+            .iter()
+            .filter(|r| r.parent_id == id && !r.text.is_empty())
+            .filter_map(|r| match r.kind {
+                3 => Some((NoteKind::Comment, r.text.clone())),
+                4 => Some((NoteKind::Diff, r.text.clone())),
+                _ => None,
+            })
+            .collect();
 
         Some(DefItem {
             id,
+            parent_id: self.parents[&id],
             definition,
             examples,
+            notes,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: u32, parent_id: u32, kind: u8) -> Record {
+        Record {
+            id,
+            parent_id,
+            text: format!("sens {id}"),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_selection_condition() {
+        let selection = Selection {
+            words: vec!["casă".to_string()],
+            ids: vec![3, 5, 8, 13],
+            from_id: Some(2),
+            ..Selection::default()
+        };
+        assert_eq!(selection.ids_page(0, 2), &[3, 5]);
+        assert_eq!(selection.ids_page(5, 2), &[8, 13]);
+        assert_eq!(selection.ids_page(6, 10), &[8, 13]);
+        assert!(selection.ids_page(13, 2).is_empty());
+
+        let (condition, params) = selection.condition(&[8, 13]);
+        assert_eq!(
+            condition,
+            " AND (e.description=? OR e.description LIKE CONCAT(?, ' %') ESCAPE '!') \
+             AND e.id IN (?,?) AND e.id>=?"
+        );
+        assert_eq!(
+            params,
+            vec![
+                Value::from("casă"),
+                Value::from("casă"),
+                Value::from(8_u32),
+                Value::from(13_u32),
+                Value::from(2_u32)
+            ]
+        );
+
+        let selection = Selection {
+            modified_since: Some(1_700_000_000),
+            ..Selection::default()
+        };
+        let (condition, params) = selection.condition(&[]);
+        assert!(condition.starts_with(" AND (e.modDate>=? OR EXISTS"));
+        assert!(!condition.contains("modDate>?"));
+        assert_eq!(params, vec![Value::from(1_700_000_000_i64); 6]);
+
+        let selection = Selection {
+            words: vec!["a_b%".to_string()],
+            ..Selection::default()
+        };
+        let (_, params) = selection.condition(&[]);
+        assert_eq!(params, vec![Value::from("a_b%"), Value::from("a!_b!%")]);
+        assert_eq!(escape_like("c!d\\"), "c!!d\\");
+
+        let (condition, params) = Selection::default().condition(&[]);
+        assert!(condition.is_empty());
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_group_by_entry() {
+        let rows = vec![
+            (5, record(1, 0, 0)),
+            (3, record(2, 0, 0)),
+            (5, record(3, 1, 0)),
+            (5, record(4, 0, 2)),
+        ];
+        let groups = group_by_entry(rows);
+        let ids = |entry_id| -> Vec<u32> { groups[&entry_id].iter().map(|r| r.id).collect() };
+        assert_eq!(groups.len(), 2);
+        assert_eq!(ids(5), vec![1, 3, 4]);
+        assert_eq!(ids(3), vec![2]);
+
+        let inflections = group_by_entry(vec![(7, "casă".to_string()), (7, "case".to_string())]);
+        assert_eq!(inflections[&7], vec!["casă", "case"]);
+        assert!(group_by_entry(Vec::<(u32, String)>::new()).is_empty());
+    }
+
+    #[test]
+    fn test_group_relations() {
+        let rows = vec![
+            (10, 1, 100, "locuință".to_string()),
+            (11, 2, 101, "bancă, bănci".to_string()),
+            (10, 3, 102, "căsuță".to_string()),
+            (10, 9, 103, "necunoscut".to_string()),
+            (12, 4, 104, "căsoi".to_string()),
+            (10, 1, 105, "clădire".to_string()),
+        ];
+        let relations = group_relations(rows);
+        assert_eq!(relations.len(), 3);
+        assert_eq!(
+            relations[&10],
+            vec![
+                (RelationKind::Synonym, 100, "locuință".to_string()),
+                (RelationKind::Diminutive, 102, "căsuță".to_string()),
+                (RelationKind::Synonym, 105, "clădire".to_string()),
+            ]
+        );
+        assert_eq!(
+            relations[&11],
+            vec![(RelationKind::Antonym, 101, "bancă".to_string())]
+        );
+        assert_eq!(relations[&12][0].0, RelationKind::Augmentative);
+    }
+
+    #[test]
+    fn test_group_paradigms() {
+        let row = |entry_id, lexeme_id, lexeme: &str, inflection: &str, form: &str| {
+            (
+                entry_id,
+                lexeme_id,
+                lexeme.to_string(),
+                "F1".to_string(),
+                inflection.to_string(),
+                form.to_string(),
+            )
+        };
+        let rows = vec![
+            row(1, 10, "casă", "singular", "casă"),
+            row(1, 10, "casă", "plural", "case"),
+            row(1, 11, "casa", "singular", "casa"),
+            // same lexeme, other entry
+            row(2, 11, "casa", "singular", "casa"),
+        ];
+        let paradigms = group_paradigms(rows);
+        let lexemes: Vec<&str> = paradigms[&1].iter().map(Paradigm::lexeme).collect();
+        assert_eq!(lexemes, vec!["casă", "casa"]);
+        let forms: Vec<(&str, &str)> = paradigms[&1][0]
+            .forms()
+            .iter()
+            .map(|form| (form.inflection(), form.form()))
+            .collect();
+        assert_eq!(forms, vec![("singular", "casă"), ("plural", "case")]);
+        assert_eq!(paradigms[&1][1].forms().len(), 1);
+        assert_eq!(paradigms[&2].len(), 1);
+        assert_eq!(paradigms[&2][0].model(), "F1");
+    }
+
+    #[test]
+    fn test_meaning_tree() {
+        let records = vec![
+            record(1, 0, 0),
+            record(2, 1, 0),
+            record(3, 2, 2),
+            record(4, 1, 5),
+            record(5, 4, 0),
+            record(6, 1, 0),
+            record(7, 0, 0),
+            record(8, 1, 3),
+        ];
+        let items: Vec<DefItem> = DefIterator::new(records).collect();
+        let tree: Vec<(u32, u32)> = items.iter().map(|item| (item.id, item.parent_id)).collect();
+        assert_eq!(tree, vec![(1, 0), (2, 1), (4, 1), (5, 1), (6, 1), (7, 0)]);
+        assert_eq!(
+            items[0].notes,
+            vec![(NoteKind::Comment, "sens 8".to_string())]
+        );
+
+        // meaning 2 skipped, its sub-meaning goes to meaning 5, numbered under it
+        let parents: HashMap<u32, u32> = HashMap::from([(1, 0), (2, 5), (3, 2), (4, 0), (5, 0)]);
+        let meanings = vec![
+            (1, 0, Meaning::new("unu")),
+            (5, 0, Meaning::new("cinci")),
+            (3, 2, Meaning::new("trei")),
+            (4, 0, Meaning::new("patru")),
+        ];
+        let nested = nest_meanings(meanings, &parents);
+        let breadcrumbs: Vec<Option<&str>> = nested.iter().map(Meaning::breadcrumb).collect();
+        assert_eq!(breadcrumbs, vec![Some("1"), Some("2"), Some("3")]);
+        let sub_meaning = &nested[1].meanings()[0];
+        assert_eq!(sub_meaning.definition(), "Trei.");
+        assert_eq!(sub_meaning.breadcrumb(), Some("2.1"));
+        assert!(nested[0].meanings().is_empty());
+    }
+}
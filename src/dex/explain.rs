@@ -0,0 +1,137 @@
+use crate::dex::{DefItem, Record, rejection::Reason, rules::Class};
+use crate::model::{Etymology, NoteKind};
+use std::fmt;
+
+/// How the records of one entry were turned into a definition, for `explain` command.
+#[derive(Default)]
+pub struct Explanation {
+    records: Vec<Record>,
+    inflections: Vec<String>,
+    // raw and parsed
+    part_of_speech: Option<(String, String)>,
+    items: Vec<ItemTrace>,
+    // record id and parsed origins
+    etymologies: Vec<(u32, String)>,
+}
+
+struct ItemTrace {
+    id: u32,
+    parent_id: u32,
+    text: String,
+    examples: Vec<String>,
+    // editorial comments and diffs
+    editorial: Vec<(NoteKind, String)>,
+    class: Class,
+    notes: Vec<String>,
+    rejected: Vec<(String, Reason)>,
+    // None when the item was skipped
+    outcome: Option<String>,
+}
+
+impl Explanation {
+    pub(super) fn source(
+        &mut self,
+        records: &[Record],
+        inflections: &[String],
+        part_of_speech: Option<(String, String)>,
+    ) {
+        self.records = records.to_vec();
+        self.inflections = inflections.to_vec();
+        self.part_of_speech = part_of_speech;
+    }
+
+    pub(super) fn item(&mut self, item: &DefItem, class: Class) {
+        self.items.push(ItemTrace {
+            id: item.id,
+            parent_id: item.parent_id,
+            text: item.definition.clone(),
+            examples: item.examples.clone(),
+            editorial: item.notes.clone(),
+            class,
+            notes: Vec::new(),
+            rejected: Vec::new(),
+            outcome: None,
+        });
+    }
+
+    /// Lookups and decisions made for current item.
+    pub(super) fn note(&mut self, note: String) {
+        if let Some(item) = self.items.last_mut() {
+            item.notes.push(note);
+        }
+    }
+
+    /// Expression or example text left out of the definition.
+    pub(super) fn rejected(&mut self, text: &str, reason: Reason) {
+        if let Some(item) = self.items.last_mut() {
+            item.rejected.push((text.to_string(), reason));
+        }
+    }
+
+    pub(super) fn etymology(&mut self, id: u32, etymology: &Etymology) {
+        let origins: Vec<String> = etymology
+            .origins()
+            .iter()
+            .map(|origin| match origin.language() {
+                Some(language) => format!("{language} {}", origin.phrase()),
+                None => origin.phrase().to_string(),
+            })
+            .collect();
+        self.etymologies.push((id, origins.join(", ")));
+    }
+
+    pub(super) fn outcome(&mut self, outcome: String) {
+        if let Some(item) = self.items.last_mut() {
+            item.outcome = Some(outcome);
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "records (id, parent id, type, text):")?;
+        for record in &self.records {
+            writeln!(
+                f,
+                "  {} {} {} {}",
+                record.id, record.parent_id, record.kind, record.text
+            )?;
+        }
+        writeln!(f, "inflected forms: {}", self.inflections.join(", "))?;
+        match &self.part_of_speech {
+            Some((raw, parsed)) => writeln!(f, "part of speech: {raw} -> {parsed}")?,
+            None => writeln!(f, "part of speech: none")?,
+        }
+
+        for (id, origins) in &self.etymologies {
+            writeln!(f, "etymology {id}: {origins}")?;
+        }
+
+        writeln!(f, "items:")?;
+        for item in &self.items {
+            write!(f, "  meaning {}", item.id)?;
+            if item.parent_id != 0 {
+                write!(f, " under {}", item.parent_id)?;
+            }
+            writeln!(f, ": {:?}", item.class)?;
+            writeln!(f, "    text: {}", item.text)?;
+            for example in &item.examples {
+                writeln!(f, "    example: {example}")?;
+            }
+            for (kind, text) in &item.editorial {
+                writeln!(f, "    {kind}: {text}")?;
+            }
+            for note in &item.notes {
+                writeln!(f, "    {note}")?;
+            }
+            for (text, reason) in &item.rejected {
+                writeln!(f, "    rejected, {reason}: {text}")?;
+            }
+            match &item.outcome {
+                Some(outcome) => writeln!(f, "    -> {outcome}")?,
+                None => writeln!(f, "    -> skipped")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use crate::dex::{AsyncDatabase, Database, Selection, rejection::Rejection};
+use crate::error::{AppError, Result};
+use crate::model::Definition;
+use log::{debug, trace};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore, mpsc};
+use tokio::task;
+
+/// Definitions ordered by entry id, with the texts rejected while building them.
+pub struct Batch {
+    pub definitions: Vec<(u32, Definition)>,
+    pub rejections: Vec<Rejection>,
+}
+
+type Work = (usize, Vec<(u32, String)>);
+
+/// Concurrent DEX extractor.
+///
+/// A pager task walks entry ids in batches and hands them to a fixed number of worker tasks,
+/// each one with its own pooled connection. Workers build definitions for disjoint batches and
+/// send them through a bounded channel; batches are numbered by the pager so that
+/// [`Extractor::next_batch`] can restore entry id order no matter which worker finishes first.
+/// The pager takes a permit of a window for every batch, given back once the batch is returned
+/// in order, so that batches completed behind a slow one stay bounded.
+///
+/// Queries run on tokio blocking threads, as with [`AsyncDatabase`]; the tasks themselves only
+/// await.
+pub struct Extractor {
+    receiver: mpsc::Receiver<(usize, Result<Batch>)>,
+    // batches completed out of order, waiting for the preceding ones
+    pending: BTreeMap<usize, Batch>,
+    next_sequence: usize,
+    // batches paged and not yet returned
+    window: Arc<Semaphore>,
+}
+
+/// Entries to extract and how to split the work.
+pub struct Plan {
+    pub selection: Selection,
+    pub base_id: u32,
+    pub limit: Option<usize>,
+    pub batch_size: usize,
+    pub concurrency: usize,
+    // attach editorial notes to meanings
+    pub notes: bool,
+}
+
+impl Extractor {
+    pub async fn start(dex: &AsyncDatabase, plan: Plan) -> Result<Self> {
+        trace!("dex::extractor::Extractor::start(dex: &AsyncDatabase, plan: Plan) -> Result<Self>");
+
+        let concurrency = plan.concurrency.clamp(1, Database::MAX_CONCURRENCY);
+        let (work_sender, work_receiver) = mpsc::channel::<Work>(concurrency);
+        let work_receiver = Arc::new(Mutex::new(work_receiver));
+        let (sender, receiver) = mpsc::channel(concurrency * 2);
+        let window = Arc::new(Semaphore::new(concurrency * 2));
+
+        // connections are acquired here so that failures are reported before any work starts
+        for _ in 0..concurrency {
+            let mut worker = dex.call(|dex| dex.try_clone()).await?;
+            worker.include_notes(plan.notes);
+            tokio::spawn(work(worker, work_receiver.clone(), sender.clone()));
+        }
+        let pager = dex.call(|dex| dex.try_clone()).await?;
+        tokio::spawn(page(pager, plan, work_sender, sender, window.clone()));
+
+        Ok(Self {
+            receiver,
+            pending: BTreeMap::new(),
+            next_sequence: 0,
+            window,
+        })
+    }
+
+    /// Next batch in entry id order or `None` when all selected entries were extracted.
+    pub async fn next_batch(&mut self) -> Result<Option<Batch>> {
+        loop {
+            if let Some(batch) = self.pending.remove(&self.next_sequence) {
+                self.next_sequence += 1;
+                self.window.add_permits(1);
+                return Ok(Some(batch));
+            }
+            match self.receiver.recv().await {
+                Some((sequence, batch)) => {
+                    self.pending.insert(sequence, batch?);
+                }
+                None if self.pending.is_empty() => return Ok(None),
+                None => return Err(AppError::Fatal("extractor batch lost")),
+            }
+        }
+    }
+}
+
+// dropping the extractor closes results channel: workers stop on next send, which closes the
+// work channel and stops the pager in turn; a pager waiting for the window is stopped by
+// closing it
+impl Drop for Extractor {
+    fn drop(&mut self) {
+        self.window.close();
+    }
+}
+
+async fn page(
+    mut dex: Database,
+    plan: Plan,
+    work_sender: mpsc::Sender<Work>,
+    sender: mpsc::Sender<(usize, Result<Batch>)>,
+    window: Arc<Semaphore>,
+) {
+    let plan = Arc::new(plan);
+    let mut base_id = plan.base_id;
+    let mut count = 0;
+    let mut sequence = 0;
+    loop {
+        let size = match plan.limit {
+            Some(limit) if count >= limit => break,
+            Some(limit) => plan.batch_size.min(limit - count),
+            None => plan.batch_size,
+        };
+        let Ok(permit) = window.acquire().await else {
+            break;
+        };
+        permit.forget();
+        let batch_plan = plan.clone();
+        let entries = task::spawn_blocking(move || {
+            let entries = dex.next_batch(base_id, &batch_plan.selection, size);
+            (dex, entries)
+        })
+        .await;
+        let entries = match entries {
+            Ok((database, Ok(entries))) => {
+                dex = database;
+                entries
+            }
+            Ok((_, Err(error))) => {
+                let _ = sender.send((sequence, Err(error))).await;
+                break;
+            }
+            Err(error) => {
+                let _ = sender.send((sequence, Err(AppError::from(error)))).await;
+                break;
+            }
+        };
+        let Some((last_id, _)) = entries.last() else {
+            break;
+        };
+        base_id = *last_id;
+        count += entries.len();
+        if work_sender.send((sequence, entries)).await.is_err() {
+            break;
+        }
+        sequence += 1;
+    }
+    debug!("pager done after {count} entries in {sequence} batches");
+}
+
+async fn work(
+    mut dex: Database,
+    work_receiver: Arc<Mutex<mpsc::Receiver<Work>>>,
+    sender: mpsc::Sender<(usize, Result<Batch>)>,
+) {
+    loop {
+        // lock is released as soon as a batch is taken, before processing it
+        let work = work_receiver.lock().await.recv().await;
+        let Some((sequence, entries)) = work else {
+            return;
+        };
+        let batch = task::spawn_blocking(move || {
+            let batch = dex.query_batch(entries).map(|definitions| Batch {
+                definitions,
+                rejections: dex.take_rejections(),
+            });
+            (dex, batch)
+        })
+        .await;
+        let batch = match batch {
+            Ok((database, batch)) => {
+                dex = database;
+                batch
+            }
+            Err(error) => {
+                let _ = sender.send((sequence, Err(AppError::from(error)))).await;
+                return;
+            }
+        };
+        if sender.send((sequence, batch)).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(entry_id: u32) -> Batch {
+        let definition = Definition::builder()
+            .entry_id(entry_id)
+            .word("casă")
+            .build()
+            .unwrap();
+        Batch {
+            definitions: vec![(entry_id, definition)],
+            rejections: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_next_batch_restores_order() {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut extractor = Extractor {
+            receiver,
+            pending: BTreeMap::new(),
+            next_sequence: 0,
+            window: Arc::new(Semaphore::new(0)),
+        };
+        for sequence in [2, 0, 1] {
+            sender
+                .send((sequence, Ok(batch(sequence as u32))))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+
+        let mut entry_ids = Vec::new();
+        while let Some(batch) = extractor.next_batch().await.unwrap() {
+            entry_ids.push(batch.definitions[0].0);
+        }
+        assert_eq!(entry_ids, vec![0, 1, 2]);
+        // permits given back for the pager, one per batch returned
+        assert_eq!(extractor.window.available_permits(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_next_batch_reports_lost_batch() {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut extractor = Extractor {
+            receiver,
+            pending: BTreeMap::new(),
+            next_sequence: 0,
+            window: Arc::new(Semaphore::new(0)),
+        };
+        sender.send((1, Ok(batch(1)))).await.unwrap();
+        drop(sender);
+        assert!(extractor.next_batch().await.is_err());
+    }
+}
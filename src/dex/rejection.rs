@@ -0,0 +1,189 @@
+use crate::error::Result;
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Source text left out of a definition because the parser could not use it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rejection {
+    pub entry_id: u32,
+    pub meaning_id: u32,
+    pub text: String,
+    pub reason: Reason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// `$phrase =$` expression without synonyms to define it.
+    ExpressionWithoutSynonyms,
+    /// Expression referencing a meaning that has no definition.
+    EmptyReferencedMeaning,
+    /// DEX query for a referenced or related meaning failed.
+    LookupFailed,
+    /// Expression matching none of the known forms.
+    UnrecognizedExpression,
+    /// `$phrase = definition$` expression given as example.
+    ExpressionAsExample,
+    /// Example matching none of the known forms.
+    UnrecognizedExample,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Reason::ExpressionWithoutSynonyms => "expression_without_synonyms",
+            Reason::EmptyReferencedMeaning => "empty_referenced_meaning",
+            Reason::LookupFailed => "lookup_failed",
+            Reason::UnrecognizedExpression => "unrecognized_expression",
+            Reason::ExpressionAsExample => "expression_as_example",
+            Reason::UnrecognizedExample => "unrecognized_example",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Rejection report of an import run: one JSON line per rejection, appended to by resumed
+/// sessions of the same run, and a summary by reason.
+///
+/// Entries processed after the last checkpoint of a crashed session are processed again when
+/// it is resumed, so their rejections can be in the report twice, and its last line can be
+/// cut short; the summary counts repeated rejections once and skips unreadable lines.
+pub struct Report {
+    path: PathBuf,
+    summary_path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub run_id: String,
+    pub total: u64,
+    // lines left incomplete by a crashed session
+    pub unreadable: u64,
+    pub by_reason: BTreeMap<Reason, u64>,
+}
+
+impl Report {
+    pub fn create(directory: &Path, run_id: &str) -> Result<Self> {
+        trace!("dex::rejection::Report::create(directory: &Path, run_id: &str) -> Result<Self>");
+        fs::create_dir_all(directory)?;
+        let name = run_id.replace(':', "-");
+        let path = directory.join(format!("{name}.rejections.jsonl"));
+        let summary_path = directory.join(format!("{name}.summary.json"));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        // a crashed session can leave a partial line, the next rejection starts on its own
+        if file.metadata()?.len() > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+        Ok(Self {
+            path,
+            summary_path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, rejection: &Rejection) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, rejection)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Write the summary of every rejection in the report, earlier sessions included.
+    pub fn finish(mut self, run_id: &str) -> Result<Summary> {
+        trace!("dex::rejection::Report::finish(self, run_id: &str) -> Result<Summary>");
+        self.writer.flush()?;
+
+        let mut summary = Summary {
+            run_id: run_id.to_string(),
+            total: 0,
+            unreadable: 0,
+            by_reason: BTreeMap::new(),
+        };
+        let mut seen = HashSet::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let Ok(rejection) = serde_json::from_str::<Rejection>(&line?) else {
+                summary.unreadable += 1;
+                continue;
+            };
+            if !seen.insert((rejection.entry_id, rejection.meaning_id, rejection.text)) {
+                continue;
+            }
+            summary.total += 1;
+            *summary.by_reason.entry(rejection.reason).or_default() += 1;
+        }
+        fs::write(
+            &self.summary_path,
+            format!("{}\n", serde_json::to_string_pretty(&summary)?),
+        )?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_summary_covers_resumed_sessions() {
+        let directory = std::env::temp_dir().join(format!("dex-report-{}", std::process::id()));
+        let run_id = "2024-01-01T00:00:00.000Z-1";
+        let rejection = |reason| Rejection {
+            entry_id: 1,
+            meaning_id: 2,
+            text: "$text".to_string(),
+            reason,
+        };
+
+        let mut report = Report::create(&directory, run_id).unwrap();
+        let path = report.path().to_path_buf();
+        report
+            .write(&rejection(Reason::UnrecognizedExample))
+            .unwrap();
+        report.finish(run_id).unwrap();
+
+        let mut report = Report::create(&directory, run_id).unwrap();
+        // entry processed again after a crash
+        report
+            .write(&rejection(Reason::UnrecognizedExample))
+            .unwrap();
+        let mut expression = rejection(Reason::ExpressionAsExample);
+        expression.text = "$phrase = definition$".to_string();
+        report.write(&expression).unwrap();
+        let summary = report.finish(run_id).unwrap();
+
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.by_reason[&Reason::UnrecognizedExample], 1);
+        assert_eq!(summary.by_reason[&Reason::ExpressionAsExample], 1);
+
+        // crashed session, cut short while writing a rejection
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"entry_id\":3,").unwrap();
+        let mut report = Report::create(&directory, run_id).unwrap();
+        let mut other = rejection(Reason::UnrecognizedExample);
+        other.entry_id = 3;
+        report.write(&other).unwrap();
+        let summary = report.finish(run_id).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.unreadable, 1);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::dex::rejection::Reason;
+use crate::error::{AppError, Result};
+use crate::model::{Etymology, Example};
+use log::{info, trace};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+// built-in rules, also the starting point for custom rules files
+const DEFAULT: &str = include_str!("../../rules.toml");
+
+const VERSION: u32 = 1;
+
+/// Parsing rules for meaning `internalRep` text, compiled and validated once when loaded.
+pub struct Rules {
+    classify: Vec<(Regex, Class)>,
+    expressions: Vec<ExpressionRule>,
+    referenced_definition: (Regex, usize),
+    examples: Vec<ExampleRule>,
+    // origin pattern with language and phrase groups
+    etymology: (Regex, usize, usize),
+    normalize: Vec<(Regex, String)>,
+}
+
+/// Classification of a definition record, selecting how it becomes a meaning or an expression.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Class {
+    // no text, the meaning is given by its synonyms
+    Empty,
+    // `$(...)$` text completed by synonyms
+    IncompleteMeaning,
+    // `(...)` or `...:` text completed by synonyms
+    MissingDefinition,
+    Expression,
+    Plain,
+}
+
+/// Source of an expression definition when the pattern does not capture it.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    Synonyms,
+    ReferencedMeaning,
+    RelatedMeaning,
+}
+
+pub struct ExpressionRule {
+    pub pattern: Regex,
+    // whole text when not set
+    pub phrase: Option<usize>,
+    pub definition: Option<usize>,
+    // meaning id, for referenced meaning fallback
+    pub reference: Option<usize>,
+    pub fallback: Option<Fallback>,
+}
+
+struct ExampleRule {
+    pattern: Regex,
+    action: ExampleAction,
+}
+
+enum ExampleAction {
+    Accept { text: usize, source: Option<usize> },
+    Reject(Reason),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    version: u32,
+    #[serde(default)]
+    classify: Vec<ClassifyEntry>,
+    #[serde(default)]
+    expressions: Vec<ExpressionEntry>,
+    referenced_definition: ReferencedDefinitionEntry,
+    #[serde(default)]
+    examples: Vec<ExampleEntry>,
+    etymology: EtymologyEntry,
+    #[serde(default)]
+    normalize: Vec<NormalizeEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClassifyEntry {
+    pattern: String,
+    class: Class,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpressionEntry {
+    pattern: String,
+    phrase: Option<usize>,
+    definition: Option<usize>,
+    reference: Option<usize>,
+    fallback: Option<Fallback>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReferencedDefinitionEntry {
+    pattern: String,
+    definition: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExampleEntry {
+    pattern: String,
+    text: Option<usize>,
+    source: Option<usize>,
+    reject: Option<Reason>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EtymologyEntry {
+    pattern: String,
+    language: usize,
+    phrase: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NormalizeEntry {
+    pattern: String,
+    replace: String,
+}
+
+impl Rules {
+    /// Load rules from given file, or the built-in ones.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        trace!("dex::rules::Rules::load(path: Option<&Path>) -> Result<Self>");
+        let rules = match path {
+            Some(path) => {
+                let source = path.display().to_string();
+                let content = fs::read_to_string(path)
+                    .map_err(|error| AppError::Rules(format!("{source}: {error}")))?;
+                Rules::parse(&content, &source)?
+            }
+            None => Rules::parse(DEFAULT, "built-in rules")?,
+        };
+        info!(
+            "parsing rules: {} classifications, {} expressions, {} examples, {} normalizations",
+            rules.classify.len(),
+            rules.expressions.len(),
+            rules.examples.len(),
+            rules.normalize.len()
+        );
+        Ok(rules)
+    }
+
+    fn parse(content: &str, source: &str) -> Result<Self> {
+        let file: RulesFile = toml::from_str(content)
+            .map_err(|error| AppError::Rules(format!("{source}: {error}")))?;
+        if file.version != VERSION {
+            return Err(AppError::Rules(format!(
+                "{source}: version {} not supported, expected {VERSION}",
+                file.version
+            )));
+        }
+
+        let mut classify = Vec::new();
+        for (index, entry) in file.classify.into_iter().enumerate() {
+            let place = format!("{source}: classify[{index}]");
+            classify.push((compile(&place, &entry.pattern, &[])?, entry.class));
+        }
+
+        let mut expressions = Vec::new();
+        for (index, entry) in file.expressions.into_iter().enumerate() {
+            let place = format!("{source}: expressions[{index}]");
+            let groups = [entry.phrase, entry.definition, entry.reference];
+            let pattern = compile(&place, &entry.pattern, &groups)?;
+            if entry.definition.is_none() && entry.fallback.is_none() {
+                return Err(AppError::Rules(format!(
+                    "{place}: either definition or fallback is required"
+                )));
+            }
+            if entry.fallback == Some(Fallback::ReferencedMeaning) && entry.reference.is_none() {
+                return Err(AppError::Rules(format!(
+                    "{place}: referenced_meaning fallback requires reference"
+                )));
+            }
+            expressions.push(ExpressionRule {
+                pattern,
+                phrase: entry.phrase,
+                definition: entry.definition,
+                reference: entry.reference,
+                fallback: entry.fallback,
+            });
+        }
+
+        let entry = file.referenced_definition;
+        let place = format!("{source}: referenced_definition");
+        let referenced_definition = (
+            compile(&place, &entry.pattern, &[Some(entry.definition)])?,
+            entry.definition,
+        );
+
+        let mut examples = Vec::new();
+        for (index, entry) in file.examples.into_iter().enumerate() {
+            let place = format!("{source}: examples[{index}]");
+            let pattern = compile(&place, &entry.pattern, &[entry.text, entry.source])?;
+            let action = match (entry.text, entry.reject) {
+                (Some(text), None) => ExampleAction::Accept {
+                    text,
+                    source: entry.source,
+                },
+                (None, Some(reason)) => ExampleAction::Reject(reason),
+                _ => {
+                    return Err(AppError::Rules(format!(
+                        "{place}: exactly one of text or reject is required"
+                    )));
+                }
+            };
+            examples.push(ExampleRule { pattern, action });
+        }
+
+        let entry = file.etymology;
+        let place = format!("{source}: etymology");
+        let etymology = (
+            compile(
+                &place,
+                &entry.pattern,
+                &[Some(entry.language), Some(entry.phrase)],
+            )?,
+            entry.language,
+            entry.phrase,
+        );
+
+        let mut normalize = Vec::new();
+        for (index, entry) in file.normalize.into_iter().enumerate() {
+            let place = format!("{source}: normalize[{index}]");
+            normalize.push((compile(&place, &entry.pattern, &[])?, entry.replace));
+        }
+
+        Ok(Self {
+            classify,
+            expressions,
+            referenced_definition,
+            examples,
+            etymology,
+            normalize,
+        })
+    }
+
+    /// Class of the first matching rule, plain meaning if none matches.
+    pub fn classify(&self, text: &str) -> Class {
+        self.classify
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(text))
+            .map_or(Class::Plain, |(_, class)| *class)
+    }
+
+    pub fn expressions(&self) -> &[ExpressionRule] {
+        &self.expressions
+    }
+
+    /// Definition of a referenced meaning; expressions keep only their definition.
+    pub fn referenced_definition(&self, definition: String) -> std::result::Result<String, Reason> {
+        let (pattern, group) = &self.referenced_definition;
+        match pattern.captures(&definition) {
+            Some(captures) => captures
+                .get(*group)
+                .map(|m| m.as_str().to_string())
+                .ok_or(Reason::EmptyReferencedMeaning),
+            None => Ok(definition),
+        }
+    }
+
+    pub fn example(&self, text: &str) -> std::result::Result<Example, Reason> {
+        for rule in &self.examples {
+            let Some(captures) = rule.pattern.captures(text) else {
+                continue;
+            };
+            match &rule.action {
+                ExampleAction::Accept {
+                    text: text_group,
+                    source,
+                } => {
+                    let Some(text) = captures.get(*text_group) else {
+                        continue;
+                    };
+                    let mut example = Example::new(&self.normalize(text.as_str()));
+                    if let Some(source) = source.and_then(|group| captures.get(group)) {
+                        example.set_source(source.as_str());
+                    }
+                    return Ok(example);
+                }
+                ExampleAction::Reject(reason) => return Err(*reason),
+            }
+        }
+        Err(Reason::UnrecognizedExample)
+    }
+
+    pub fn etymology(&self, text: &str) -> Etymology {
+        let (pattern, language_group, phrase_group) = &self.etymology;
+        // abbreviation markup, e.g. `#fr.#`, is dropped from the text
+        let mut etymology = Etymology::new(&self.normalize(text).replace('#', ""));
+        let mut language: Option<String> = None;
+        for captures in pattern.captures_iter(text) {
+            if let Some(abbreviation) = captures.get(*language_group) {
+                let abbreviation = abbreviation.as_str().trim();
+                language = Some(abbreviation.trim_end_matches('.').to_lowercase());
+            }
+            if let Some(phrase) = captures.get(*phrase_group) {
+                let phrase = phrase.as_str().trim();
+                if !phrase.is_empty() {
+                    etymology.add_origin(language.as_deref(), phrase);
+                }
+            }
+        }
+        etymology
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (pattern, replace) in &self.normalize {
+            text = pattern.replace_all(&text, replace.as_str()).to_string();
+        }
+        text
+    }
+}
+
+fn compile(place: &str, pattern: &str, groups: &[Option<usize>]) -> Result<Regex> {
+    let regex = Regex::new(pattern)
+        .map_err(|error| AppError::Rules(format!("{place}: invalid pattern: {error}")))?;
+    if let Some(group) = groups
+        .iter()
+        .flatten()
+        .find(|group| **group >= regex.captures_len())
+    {
+        return Err(AppError::Rules(format!(
+            "{place}: pattern has no capture group {group}"
+        )));
+    }
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Rules {
+        Rules::parse(DEFAULT, "built-in rules").unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        let rules = rules();
+        assert_eq!(rules.classify(""), Class::Empty);
+        assert_eq!(
+            rules.classify("$(Despre oameni)$ "),
+            Class::IncompleteMeaning
+        );
+        assert_eq!(rules.classify("(Despre oameni)"), Class::MissingDefinition);
+        assert_eq!(rules.classify("Care are:"), Class::MissingDefinition);
+        assert_eq!(
+            rules.classify("$a da ortul popii$ A muri."),
+            Class::Expression
+        );
+        assert_eq!(rules.classify("Clădire de locuit."), Class::Plain);
+    }
+
+    #[test]
+    fn test_example_and_normalize() {
+        let rules = rules();
+        let example = rules.example("Și-a luat \"casă\" [12]$. (Autor)").unwrap();
+        assert_eq!(example.text(), "Și-a luat ‘casă’ ");
+        assert_eq!(example.source(), Some("Autor"));
+        assert_eq!(
+            rules.example("$casă de bani =").unwrap_err(),
+            Reason::ExpressionAsExample
+        );
+        assert_eq!(
+            rules.example("fără marcaj").unwrap_err(),
+            Reason::UnrecognizedExample
+        );
+    }
+
+    #[test]
+    fn test_etymology() {
+        let etymology = rules().etymology("Din #fr.# $maison$, $maisonnette$, #Lat.# $mansio$.");
+        assert_eq!(
+            etymology.text(),
+            "Din fr. maison, maisonnette, Lat. mansio."
+        );
+        let origins: Vec<(Option<&str>, &str)> = etymology
+            .origins()
+            .iter()
+            .map(|origin| (origin.language(), origin.phrase()))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                (Some("fr"), "maison"),
+                (Some("fr"), "maisonnette"),
+                (Some("lat"), "mansio")
+            ]
+        );
+        assert!(rules().etymology("Formație internă.").origins().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let error = Rules::parse(&DEFAULT.replace("'^$'", "'^($'"), "rules.toml")
+            .err()
+            .unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("rules.toml: classify[0]: invalid pattern")
+        );
+
+        let error = Rules::parse(
+            &DEFAULT.replace("definition = 2", "definition = 3"),
+            "rules.toml",
+        )
+        .err()
+        .unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("expressions[0]: pattern has no capture group 3")
+        );
+
+        let error = Rules::parse(&DEFAULT.replace("version = 1", "version = 2"), "rules.toml")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("version 2 not supported"));
+    }
+}
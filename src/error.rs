@@ -14,9 +14,42 @@ pub enum AppError {
     #[error("MongoDB ser error: {0}")]
     MongoSer(#[from] mongodb::bson::ser::Error),
 
+    #[error("MongoDB de error: {0}")]
+    MongoDe(#[from] mongodb::bson::de::Error),
+
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Invalid parsing rules: {0}")]
+    Rules(String),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Task error: {0}")]
+    Join(#[from] tokio::task::JoinError),
+
     #[error("Regex error: {0}")]
     Regex(#[from] regex::Error),
 
+    #[error("Knowledge database write error: {0}")]
+    Write(String),
+
+    #[error("Invalid input: {0}")]
+    Input(String),
+
+    #[error("Verification failed: {0}")]
+    Verify(String),
+
+    #[error("Aborted: {0}")]
+    Aborted(String),
+
+    #[error("Interrupted by termination signal")]
+    Interrupted,
+
     #[error("Unrecoverable error on {0}")]
     Fatal(&'static str),
 }
@@ -1,31 +1,729 @@
-use crate::{error::Result, model::Definition};
-use log::trace;
+pub(crate) mod index;
+pub(crate) mod writer;
+
+use crate::{
+    config::KbConfig,
+    dex::Selection,
+    error::{AppError, Result},
+    model::{Definition, schema::Schema},
+    util::time::DateTime,
+};
+use futures::{Stream, TryStreamExt};
+use index::{ENTRY_ID, INDEXES, IndexState};
+use log::{info, trace, warn};
 use mongodb::{
-    Client, Collection,
-    bson::{Document, to_document},
+    Client, Collection, IndexModel,
+    bson::{Bson, Document, doc, from_document, to_document},
+    error::ErrorKind,
+    options::{
+        Acknowledgment, ClientOptions, CreateCollectionOptions, Credential, DeleteOptions,
+        FindOptions, ReplaceOptions, UpdateOptions, ValidationAction, ValidationLevel,
+        WriteConcern,
+    },
 };
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::HashSet;
+use writer::{WriteMode, Writer};
+
+// name of the sync document in checkpoints collection
+const SYNC: &str = "sync";
+
+// documents of entries removed from DEX, when kept as tombstones, have this field set
+const DELETED_AT: &str = "deleted_at";
+
+// suffixes of the collections used by rebuilds, next to the live one
+const STAGING: &str = "_staging";
+const PREVIOUS: &str = "_previous";
+
+// server error code of commands on missing collections
+const NAMESPACE_NOT_FOUND: i32 = 26;
+
+// entry ids per delete or update statement
+const MAX_IDS: usize = 1000;
 
 pub struct Database {
+    database: mongodb::Database,
     collection: Collection<Document>,
+    checkpoints: Collection<Document>,
+    batch_size: usize,
+    // server default when not configured
+    write_concern: Option<WriteConcern>,
+}
+
+/// Progress of an import run, saved periodically so that an interrupted run can be resumed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    pub run_id: String,
+    // last entry id sent to knowledge database; entries are imported in ascending id order
+    pub entry_id: u32,
+    pub count: u64,
+    // definitions rejected by knowledge database
+    #[serde(default)]
+    pub failed: u64,
+    pub completed: bool,
+    pub updated_at: String,
+    // rebuild runs write into staging collection
+    #[serde(default)]
+    pub rebuild: bool,
+    // DEX server unix time when the run started
+    #[serde(default)]
+    pub started_at: i64,
+    // incremental runs: entries changed after this DEX unix time
+    #[serde(default)]
+    pub modified_since: Option<i64>,
+    // entries of the run as first started, resumed runs walk the same ones
+    #[serde(default)]
+    pub selection: Selection,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    // run over all entries, decided when it starts; only those update the sync point
+    #[serde(default)]
+    pub complete: bool,
+    // options the documents of the run are written with, kept by resumed runs
+    #[serde(default)]
+    pub notes: bool,
+    #[serde(default)]
+    pub mode: WriteMode,
+}
+
+/// Entry stored in knowledge database.
+#[derive(Debug)]
+pub struct StoredEntry {
+    pub entry_id: u32,
+    pub word: String,
+    pub tombstone: bool,
+}
+
+/// Last import run that went through all entries, the reference for incremental runs.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncPoint {
+    pub run_id: String,
+    // DEX server unix time when the run started; later changes are not in knowledge database
+    pub started_at: i64,
+    pub completed_at: String,
+}
+
+impl Checkpoint {
+    pub fn new(
+        started_at: i64,
+        modified_since: Option<i64>,
+        rebuild: bool,
+        selection: Selection,
+        limit: Option<usize>,
+    ) -> Self {
+        let now = DateTime::now().iso8601();
+        let complete = !selection.restricted() && limit.is_none();
+        Self {
+            run_id: format!("{now}-{}", std::process::id()),
+            entry_id: 0,
+            count: 0,
+            failed: 0,
+            completed: false,
+            updated_at: now,
+            rebuild,
+            started_at,
+            modified_since,
+            selection,
+            limit,
+            complete,
+            notes: false,
+            mode: WriteMode::default(),
+        }
+    }
+
+    pub fn advance(&mut self, entry_id: u32, written: u64, failed: u64) {
+        self.entry_id = entry_id;
+        self.count += written;
+        self.failed += failed;
+        self.updated_at = DateTime::now().iso8601();
+    }
 }
 
 impl Database {
-    const DATABASE: &'static str = "kb";
-    const COLLECTION: &'static str = "data";
+    pub async fn try_new(config: &KbConfig) -> Result<Self> {
+        trace!("kb::Database::try_new(config: &KbConfig) -> Result<Self>");
+        let mut options = ClientOptions::parse(&config.url).await?;
+        if let Some(user) = &config.user {
+            options.credential = Some(
+                Credential::builder()
+                    .username(user.clone())
+                    .password(
+                        config
+                            .password
+                            .as_ref()
+                            .map(|password| password.expose().to_string()),
+                    )
+                    .build(),
+            );
+        }
+        let client = Client::with_options(options)?;
+        let database = client.database(&config.database);
+        let collection = database.collection::<Document>(&config.collection);
+        let checkpoints = database.collection::<Document>(&config.checkpoints);
+
+        let write_concern = match (&config.write_concern, config.journal) {
+            (None, None) => None,
+            (w, journal) => Some(
+                WriteConcern::builder()
+                    .w(w.as_deref().map(|w| match w.parse::<u32>() {
+                        Ok(nodes) => Acknowledgment::Nodes(nodes),
+                        Err(_) => Acknowledgment::from(w.to_string()),
+                    }))
+                    .journal(journal)
+                    .build(),
+            ),
+        };
+
+        Ok(Self {
+            database,
+            collection,
+            checkpoints,
+            batch_size: config.batch_size,
+            write_concern,
+        })
+    }
+
+    /// Same knowledge database, writing into the staging collection of rebuilds.
+    pub fn staging(&self) -> Self {
+        self.with_collection(&format!("{}{STAGING}", self.collection.name()))
+    }
+
+    fn previous(&self) -> Self {
+        self.with_collection(&format!("{}{PREVIOUS}", self.collection.name()))
+    }
+
+    fn with_collection(&self, name: &str) -> Self {
+        Self {
+            database: self.database.clone(),
+            collection: self.database.collection(name),
+            checkpoints: self.checkpoints.clone(),
+            batch_size: self.batch_size,
+            write_concern: self.write_concern.clone(),
+        }
+    }
+
+    pub fn collection_name(&self) -> &str {
+        self.collection.name()
+    }
+
+    pub fn writer(&self, mode: WriteMode) -> Writer {
+        Writer::new(
+            self.database.clone(),
+            self.collection.clone(),
+            self.write_concern.clone(),
+            mode,
+            self.batch_size,
+        )
+    }
+
+    /// Set the `$jsonSchema` validator of the collection, creating the collection if missing,
+    /// so that documents written by any tool keep the shape of [`Definition`].
+    ///
+    /// Installed before every import and on collections swapped in by [`Database::promote`]
+    /// and [`Database::rollback`]; not on connection, so that read-only users can still look
+    /// definitions up.
+    pub async fn install_validator(&self) -> Result<()> {
+        trace!("kb::Database::install_validator(&self) -> Result<()>");
+        let validator = doc! { "$jsonSchema": collection_schema()? };
+        let command = doc! {
+            "collMod": self.collection.name(),
+            "validator": validator.clone(),
+            "validationLevel": "strict",
+            "validationAction": "error",
+        };
+        match self.database.run_command(command, None).await {
+            Ok(_) => Ok(()),
+            Err(error) if missing_collection(&error) => {
+                let options = CreateCollectionOptions::builder()
+                    .validator(validator)
+                    .validation_level(ValidationLevel::Strict)
+                    .validation_action(ValidationAction::Error)
+                    .build();
+                self.database
+                    .create_collection(self.collection.name(), options)
+                    .await?;
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Create the unique index on source entry id, if missing, so that a definition can be
+    /// stored only once.
+    pub async fn create_entry_index(&self) -> Result<()> {
+        trace!("kb::Database::create_entry_index(&self) -> Result<()>");
+        self.collection.create_index(ENTRY_ID.model(), None).await?;
+        Ok(())
+    }
+
+    /// Declared indexes and the undeclared ones found on the collection, by name.
+    pub async fn indexes(&self) -> Result<Vec<(String, IndexState)>> {
+        trace!("kb::Database::indexes(&self) -> Result<Vec<(String, IndexState)>>");
+        let existing = self.existing_indexes().await?;
+        let mut indexes: Vec<(String, IndexState)> = INDEXES
+            .iter()
+            .map(|spec| {
+                let state = match existing.iter().find(|(name, _)| name == spec.name) {
+                    Some((_, index)) if spec.matches(index) => IndexState::Declared,
+                    Some(_) => IndexState::Different,
+                    None => IndexState::Missing,
+                };
+                (spec.name.to_string(), state)
+            })
+            .collect();
+        indexes.extend(
+            existing
+                .into_iter()
+                .filter(|(name, _)| !INDEXES.iter().any(|spec| spec.name == name))
+                .map(|(name, _)| (name, IndexState::Undeclared)),
+        );
+        Ok(indexes)
+    }
+
+    /// Create missing declared indexes and recreate the ones with other keys or options.
+    pub async fn ensure_indexes(&self) -> Result<()> {
+        trace!("kb::Database::ensure_indexes(&self) -> Result<()>");
+        let existing = self.existing_indexes().await?;
+        for spec in &INDEXES {
+            match existing.iter().find(|(name, _)| name == spec.name) {
+                Some((_, index)) if spec.matches(index) => continue,
+                Some(_) => {
+                    warn!(
+                        "index {} differs from declaration, recreating it",
+                        spec.name
+                    );
+                    self.collection.drop_index(spec.name, None).await?;
+                }
+                None => {}
+            }
+            info!("creating index {} on {}", spec.name, self.collection.name());
+            self.collection.create_index(spec.model(), None).await?;
+        }
+        Ok(())
+    }
+
+    /// Drop every index but the one on `_id`, then create the declared ones.
+    pub async fn rebuild_indexes(&self) -> Result<()> {
+        trace!("kb::Database::rebuild_indexes(&self) -> Result<()>");
+        for (name, _) in self.existing_indexes().await? {
+            info!("dropping index {name} on {}", self.collection.name());
+            self.collection.drop_index(name, None).await?;
+        }
+        let models: Vec<IndexModel> = INDEXES.iter().map(|spec| spec.model()).collect();
+        self.collection.create_indexes(models, None).await?;
+        Ok(())
+    }
+
+    // by name, without the one on _id; none if the collection does not exist yet
+    async fn existing_indexes(&self) -> Result<Vec<(String, IndexModel)>> {
+        let indexes: Vec<IndexModel> = match self.collection.list_indexes(None).await {
+            Ok(cursor) => cursor.try_collect().await?,
+            Err(error) if missing_collection(&error) => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(indexes
+            .into_iter()
+            .filter_map(|index| {
+                let name = index.options.as_ref()?.name.clone()?;
+                (name != "_id_").then_some((name, index))
+            })
+            .collect())
+    }
+
+    pub async fn drop_collection(&self) -> Result<()> {
+        trace!("kb::Database::drop_collection(&self) -> Result<()>");
+        self.collection.drop(None).await?;
+        Ok(())
+    }
+
+    /// Replace this, live, collection with given staging one.
+    ///
+    /// Live collection is first copied over the previous one, kept as rollback target, then
+    /// staging is renamed over it in a single step, so readers never see a partial collection.
+    pub async fn promote(&self, staging: &Database) -> Result<()> {
+        trace!("kb::Database::promote(&self, staging: &Database) -> Result<()>");
+        let previous = self.previous();
+        if self.exists().await? {
+            self.collection
+                .aggregate(vec![doc! { "$out": previous.collection.name() }], None)
+                .await?;
+            // `$out` copies documents only
+            previous.install_validator().await?;
+            previous.ensure_indexes().await?;
+        }
+        self.rename(&staging.collection, &self.collection).await
+    }
+
+    /// Replace this, live, collection with the one it replaced on last promotion.
+    pub async fn rollback(&self) -> Result<()> {
+        trace!("kb::Database::rollback(&self) -> Result<()>");
+        let previous = self.previous();
+        if !previous.exists().await? {
+            return Err(AppError::Input(format!(
+                "no {} collection to roll back to",
+                previous.collection.name()
+            )));
+        }
+        self.rename(&previous.collection, &self.collection).await?;
+        // previous collections copied before validators were installed on them
+        self.install_validator().await
+    }
+
+    async fn exists(&self) -> Result<bool> {
+        let names = self
+            .database
+            .list_collection_names(doc! { "name": self.collection.name() })
+            .await?;
+        Ok(!names.is_empty())
+    }
 
-    pub async fn try_new(url: &str) -> Result<Self> {
-        trace!("kb::Database::try_new(url: &str) -> Result<Self>");
-        let client = Client::with_uri_str(url).await?;
-        let database = client.database(Database::DATABASE);
-        let collection = database.collection::<Document>(Database::COLLECTION);
+    // atomic, target collection is dropped
+    async fn rename(&self, from: &Collection<Document>, to: &Collection<Document>) -> Result<()> {
+        let database = self.database.name();
+        let command = doc! {
+            "renameCollection": format!("{database}.{}", from.name()),
+            "to": format!("{database}.{}", to.name()),
+            "dropTarget": true,
+        };
+        from.client()
+            .database("admin")
+            .run_command(command, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Number of definitions, tombstones excluded.
+    pub async fn count(&self) -> Result<u64> {
+        trace!("kb::Database::count(&self) -> Result<u64>");
+        Ok(self.collection.count_documents(live(doc! {}), None).await?)
+    }
+
+    /// Number of definitions for every distinct value of given field, most frequent first,
+    /// tombstones excluded.
+    pub async fn count_by(&self, field: &str) -> Result<Vec<(Option<String>, u64)>> {
+        trace!("kb::Database::count_by(&self, field: &str) -> Result<Vec<(Option<String>, u64)>>");
+        let pipeline = vec![
+            doc! { "$match": live(doc! {}) },
+            doc! { "$group": { "_id": format!("${field}"), "count": { "$sum": 1 } } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+        ];
+        let groups: Vec<Document> = self
+            .collection
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(groups
+            .into_iter()
+            .map(|group| {
+                let value = group.get_str("_id").ok().map(str::to_string);
+                let count = match group.get("count") {
+                    Some(Bson::Int32(count)) => *count as u64,
+                    Some(Bson::Int64(count)) => *count as u64,
+                    _ => 0,
+                };
+                (value, count)
+            })
+            .collect())
+    }
+
+    pub async fn words(&self) -> Result<HashSet<String>> {
+        trace!("kb::Database::words(&self) -> Result<HashSet<String>>");
+        let words = self
+            .collection
+            .distinct("word", live(doc! {}), None)
+            .await?;
+        Ok(words
+            .into_iter()
+            .filter_map(|word| match word {
+                Bson::String(word) => Some(word),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Entry id and word of every document keyed by entry id, tombstones included.
+    pub async fn entries(&self) -> Result<Vec<StoredEntry>> {
+        trace!("kb::Database::entries(&self) -> Result<Vec<StoredEntry>>");
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 0, "entry_id": 1, "word": 1, DELETED_AT: 1 })
+            .sort(doc! { "entry_id": 1 })
+            .build();
+        let documents: Vec<Document> = self
+            .collection
+            .find(doc! { "entry_id": { "$exists": true } }, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(documents
+            .into_iter()
+            .filter_map(|document| {
+                let entry_id = match document.get("entry_id") {
+                    Some(Bson::Int32(id)) => u32::try_from(*id).ok()?,
+                    Some(Bson::Int64(id)) => u32::try_from(*id).ok()?,
+                    _ => return None,
+                };
+                Some(StoredEntry {
+                    entry_id,
+                    word: document.get_str("word").unwrap_or_default().to_string(),
+                    tombstone: document.contains_key(DELETED_AT),
+                })
+            })
+            .collect())
+    }
+
+    /// Number of documents without entry id, left by imports that predate it.
+    pub async fn count_legacy(&self) -> Result<u64> {
+        trace!("kb::Database::count_legacy(&self) -> Result<u64>");
+        Ok(self
+            .collection
+            .count_documents(doc! { "entry_id": { "$exists": false } }, None)
+            .await?)
+    }
+
+    /// Delete documents without entry id; returns the number of deleted documents.
+    pub async fn delete_legacy(&self) -> Result<u64> {
+        trace!("kb::Database::delete_legacy(&self) -> Result<u64>");
+        let options = DeleteOptions::builder()
+            .write_concern(self.write_concern.clone())
+            .build();
+        let result = self
+            .collection
+            .delete_many(doc! { "entry_id": { "$exists": false } }, options)
+            .await?;
+        Ok(result.deleted_count)
+    }
+
+    /// Delete documents of given entries; returns the number of deleted documents.
+    pub async fn delete_entries(&self, entry_ids: &[u32]) -> Result<u64> {
+        trace!("kb::Database::delete_entries(&self, entry_ids: &[u32]) -> Result<u64>");
+        let options = DeleteOptions::builder()
+            .write_concern(self.write_concern.clone())
+            .build();
+        let mut count = 0;
+        for chunk in entry_ids.chunks(MAX_IDS) {
+            let result = self
+                .collection
+                .delete_many(doc! { "entry_id": { "$in": chunk } }, options.clone())
+                .await?;
+            count += result.deleted_count;
+        }
+        Ok(count)
+    }
+
+    /// Mark documents of given entries as deleted from DEX; a later import of the same entry
+    /// replaces the tombstone. Returns the number of marked documents.
+    pub async fn tombstone_entries(&self, entry_ids: &[u32]) -> Result<u64> {
+        trace!("kb::Database::tombstone_entries(&self, entry_ids: &[u32]) -> Result<u64>");
+        let options = UpdateOptions::builder()
+            .write_concern(self.write_concern.clone())
+            .build();
+        let deleted_at = DateTime::now().iso8601();
+        let mut count = 0;
+        for chunk in entry_ids.chunks(MAX_IDS) {
+            let result = self
+                .collection
+                .update_many(
+                    live(doc! { "entry_id": { "$in": chunk } }),
+                    doc! { "$set": { DELETED_AT: &deleted_at } },
+                    options.clone(),
+                )
+                .await?;
+            count += result.modified_count;
+        }
+        Ok(count)
+    }
+
+    pub async fn find_by_entry_id(&self, entry_id: u32) -> Result<Option<Definition>> {
+        trace!(
+            "kb::Database::find_by_entry_id(&self, entry_id: u32) -> Result<Option<Definition>>"
+        );
+        Ok(self
+            .definitions()
+            .find_one(live(doc! { "entry_id": entry_id }), None)
+            .await?)
+    }
+
+    pub async fn find_by_word(&self, word: &str) -> Result<Vec<Definition>> {
+        trace!("kb::Database::find_by_word(&self, word: &str) -> Result<Vec<Definition>>");
+        self.find_definitions(live(doc! { "word": word }), None)
+            .await
+    }
+
+    /// Definitions having given inflected form, exactly; those imported before forms were
+    /// stored are not found until imported again.
+    pub async fn find_by_form(&self, form: &str) -> Result<Vec<Definition>> {
+        trace!("kb::Database::find_by_form(&self, form: &str) -> Result<Vec<Definition>>");
+        self.find_definitions(live(doc! { "forms": form }), None)
+            .await
+    }
+
+    /// Definitions with words starting with given prefix, in word order.
+    pub async fn find_by_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<Definition>> {
+        trace!(
+            "kb::Database::find_by_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<Definition>>"
+        );
+        let filter = live(doc! { "word": { "$regex": format!("^{}", regex::escape(prefix)) } });
+        let options = FindOptions::builder()
+            .sort(doc! { "word": 1, "entry_id": 1 })
+            .limit(limit)
+            .build();
+        self.find_definitions(filter, options).await
+    }
+
+    /// Every definition in entry id order, read in pages of batch size so that no cursor is
+    /// kept open between pages.
+    pub fn stream_definitions(&self) -> impl Stream<Item = Result<Definition>> + use<> {
+        trace!("kb::Database::stream_definitions(&self) -> impl Stream<Item = Result<Definition>>");
+        let collection = self.definitions();
+        let page_size = self.batch_size as i64;
+        async_stream::try_stream! {
+            let mut last_entry_id: Option<u32> = None;
+            loop {
+                let filter = match last_entry_id {
+                    Some(entry_id) => live(doc! { "entry_id": { "$gt": entry_id } }),
+                    None => live(doc! {}),
+                };
+                let options = FindOptions::builder()
+                    .sort(doc! { "entry_id": 1 })
+                    .limit(page_size)
+                    .build();
+                let page: Vec<Definition> =
+                    collection.find(filter, options).await?.try_collect().await?;
+                let Some(last) = page.last() else {
+                    break;
+                };
+                last_entry_id = Some(last.entry_id());
+                for definition in page {
+                    yield definition;
+                }
+            }
+        }
+    }
 
-        Ok(Self { collection })
+    fn definitions(&self) -> Collection<Definition> {
+        self.collection.clone_with_type()
     }
 
-    pub async fn insert(&self, definition: &Definition) -> Result<()> {
-        trace!("kb::Database::insert(&self, definition: &Definition) -> Result<()>");
-        let doc = to_document(definition)?;
-        let _ = self.collection.insert_one(doc, None).await?;
+    async fn find_definitions(
+        &self,
+        filter: Document,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<Vec<Definition>> {
+        Ok(self
+            .definitions()
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    pub async fn load_checkpoint(&self, name: &str) -> Result<Option<Checkpoint>> {
+        trace!("kb::Database::load_checkpoint(&self, name: &str) -> Result<Option<Checkpoint>>");
+        self.load_state(name).await
+    }
+
+    pub async fn save_checkpoint(&self, name: &str, checkpoint: &Checkpoint) -> Result<()> {
+        trace!(
+            "kb::Database::save_checkpoint(&self, name: &str, checkpoint: &Checkpoint) -> Result<()>"
+        );
+        self.save_state(name, checkpoint).await
+    }
+
+    pub async fn load_sync(&self) -> Result<Option<SyncPoint>> {
+        trace!("kb::Database::load_sync(&self) -> Result<Option<SyncPoint>>");
+        self.load_state(SYNC).await
+    }
+
+    pub async fn save_sync(&self, sync: &SyncPoint) -> Result<()> {
+        trace!("kb::Database::save_sync(&self, sync: &SyncPoint) -> Result<()>");
+        self.save_state(SYNC, sync).await
+    }
+
+    // run state documents share checkpoints collection, keyed by name
+    async fn load_state<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        match self
+            .checkpoints
+            .find_one(doc! { "_id": name }, None)
+            .await?
+        {
+            Some(document) => Ok(Some(from_document(document)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save_state<T: Serialize>(&self, name: &str, state: &T) -> Result<()> {
+        let mut document = to_document(state)?;
+        document.insert("_id", name);
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.checkpoints
+            .replace_one(doc! { "_id": name }, document, options)
+            .await?;
         Ok(())
     }
 }
+
+/// Restrict filter to documents that are not tombstones.
+fn live(mut filter: Document) -> Document {
+    filter.insert(DELETED_AT, doc! { "$exists": false });
+    filter
+}
+
+/// Schema of knowledge database documents: a definition, with the fields added by the
+/// database and by reconciliation.
+pub fn collection_schema() -> Result<Document> {
+    let mut schema = Definition::schema();
+    let properties = schema
+        .get_document_mut("properties")
+        .map_err(|_| AppError::Fatal("definition schema properties"))?;
+    properties.insert("_id", doc! { "bsonType": "objectId" });
+    properties.insert(DELETED_AT, doc! { "bsonType": "string" });
+    Ok(schema)
+}
+
+fn missing_collection(error: &mongodb::error::Error) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Command(error) if error.code == NAMESPACE_NOT_FOUND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_complete() {
+        assert!(Checkpoint::new(0, None, false, Selection::default(), None).complete);
+
+        let selection = Selection {
+            from_id: Some(5000),
+            ..Selection::default()
+        };
+        let checkpoint = Checkpoint::new(0, None, false, selection, None);
+        assert!(!checkpoint.complete);
+        assert!(!Checkpoint::new(0, None, false, Selection::default(), Some(10)).complete);
+
+        // saved before the flag existed: never taken as complete
+        let mut document = to_document(&checkpoint).unwrap();
+        document.remove("complete");
+        document.remove("selection");
+        assert!(!from_document::<Checkpoint>(document).unwrap().complete);
+    }
+
+    #[test]
+    fn test_checkpoint_write_options() {
+        let mut checkpoint = Checkpoint::new(0, None, false, Selection::default(), None);
+        checkpoint.notes = true;
+        checkpoint.mode = WriteMode::Insert;
+        let mut document = to_document(&checkpoint).unwrap();
+        assert_eq!(document.get_str("mode").unwrap(), "insert");
+        let resumed = from_document::<Checkpoint>(document.clone()).unwrap();
+        assert!(resumed.notes);
+        assert_eq!(resumed.mode, WriteMode::Insert);
+
+        // saved before the options were kept: defaults of import
+        document.remove("notes");
+        document.remove("mode");
+        let resumed = from_document::<Checkpoint>(document).unwrap();
+        assert!(!resumed.notes);
+        assert_eq!(resumed.mode, WriteMode::Upsert);
+    }
+}
@@ -0,0 +1,165 @@
+use mongodb::{
+    IndexModel,
+    bson::{Document, doc},
+    options::IndexOptions,
+};
+
+/// Index the knowledge database collection is expected to have.
+pub struct IndexSpec {
+    pub name: &'static str,
+    field: &'static str,
+    unique: bool,
+    // only documents having the field are indexed, e.g. not the legacy ones without entry id
+    partial: bool,
+    // text indexes only
+    language: Option<&'static str>,
+}
+
+/// Unique source entry id, needed by upserts.
+pub const ENTRY_ID: IndexSpec = IndexSpec {
+    name: "entry_id",
+    field: "entry_id",
+    unique: true,
+    partial: true,
+    language: None,
+};
+
+/// Indexes created by the importer; others found on the collection are left alone.
+pub const INDEXES: [IndexSpec; 5] = [
+    ENTRY_ID,
+    // exact form lookups, one index entry per form
+    IndexSpec {
+        name: "forms",
+        field: "forms",
+        unique: false,
+        partial: false,
+        language: None,
+    },
+    // free text search only, it drops stop words and stems the others
+    IndexSpec {
+        name: "key_text",
+        field: "key",
+        unique: false,
+        partial: false,
+        language: Some("romanian"),
+    },
+    IndexSpec {
+        name: "word",
+        field: "word",
+        unique: false,
+        partial: false,
+        language: None,
+    },
+    IndexSpec {
+        name: "part_of_speech",
+        field: "part_of_speech",
+        unique: false,
+        partial: false,
+        language: None,
+    },
+];
+
+/// How an index found on the collection compares with the declared ones.
+#[derive(Debug, PartialEq)]
+pub enum IndexState {
+    Declared,
+    Missing,
+    // same name, other keys or options
+    Different,
+    Undeclared,
+}
+
+impl IndexSpec {
+    pub fn keys(&self) -> Document {
+        match self.language {
+            Some(_) => doc! { self.field: "text" },
+            None => doc! { self.field: 1 },
+        }
+    }
+
+    fn partial_filter(&self) -> Option<Document> {
+        self.partial
+            .then(|| doc! { self.field: { "$exists": true } })
+    }
+
+    pub fn model(&self) -> IndexModel {
+        IndexModel::builder()
+            .keys(self.keys())
+            .options(
+                IndexOptions::builder()
+                    .name(self.name.to_string())
+                    .unique(self.unique.then_some(true))
+                    .partial_filter_expression(self.partial_filter())
+                    .default_language(self.language.map(str::to_string))
+                    .build(),
+            )
+            .build()
+    }
+
+    /// Whether an existing index is this one; text indexes are listed with internal keys and
+    /// the indexed fields as weights.
+    pub fn matches(&self, index: &IndexModel) -> bool {
+        let options = index.options.as_ref();
+        if options.and_then(|options| options.name.as_deref()) != Some(self.name) {
+            return false;
+        }
+        if options.and_then(|options| options.unique).unwrap_or(false) != self.unique {
+            return false;
+        }
+        if options.and_then(|options| options.partial_filter_expression.clone())
+            != self.partial_filter()
+        {
+            return false;
+        }
+        match self.language {
+            Some(language) => {
+                options.and_then(|options| options.default_language.as_deref()) == Some(language)
+                    && options
+                        .and_then(|options| options.weights.as_ref())
+                        .is_some_and(|weights| {
+                            weights.len() == 1 && weights.contains_key(self.field)
+                        })
+            }
+            None => index.keys == self.keys(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_listed_text_index() {
+        // as returned by listIndexes
+        let index = IndexModel::builder()
+            .keys(doc! { "_fts": "text", "_ftsx": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("key_text".to_string())
+                    .default_language("romanian".to_string())
+                    .weights(doc! { "key": 1 })
+                    .build(),
+            )
+            .build();
+        assert!(INDEXES[2].matches(&index));
+
+        let mut english = index.clone();
+        english.options.as_mut().unwrap().default_language = Some("english".to_string());
+        assert!(!INDEXES[2].matches(&english));
+    }
+
+    #[test]
+    fn test_matches_unique() {
+        assert!(ENTRY_ID.matches(&ENTRY_ID.model()));
+
+        let mut index = ENTRY_ID.model();
+        index.options.as_mut().unwrap().unique = None;
+        assert!(!ENTRY_ID.matches(&index));
+
+        // unique over every document, failing on legacy documents without entry id
+        let mut index = ENTRY_ID.model();
+        index.options.as_mut().unwrap().partial_filter_expression = None;
+        assert!(!ENTRY_ID.matches(&index));
+    }
+}
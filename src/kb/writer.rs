@@ -0,0 +1,210 @@
+use crate::error::{AppError, Result};
+use crate::model::Definition;
+use clap::ValueEnum;
+use log::{debug, trace, warn};
+use mongodb::{
+    Collection, Database,
+    bson::{self, Bson, Document, doc, to_bson, to_document},
+    error::ErrorKind,
+    options::{InsertManyOptions, WriteConcern},
+};
+use serde::{Deserialize, Serialize};
+
+// BSON document size limit, less room for the rest of the update command
+const MAX_COMMAND_SIZE: usize = 16 * 1024 * 1024 - 64 * 1024;
+
+// server `maxWriteBatchSize`, statements per write command
+const MAX_STATEMENTS: usize = 100_000;
+
+/// How definitions are stored into knowledge database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteMode {
+    /// Plain insert; entries already in knowledge database are reported as failures.
+    Insert,
+    /// Replace the document with the same entry id, or insert it if missing.
+    #[default]
+    Upsert,
+}
+
+/// Buffered knowledge database writer.
+///
+/// Definitions are collected in memory and sent with a single unordered bulk write once the
+/// buffer is full, or on explicit [`Writer::flush`]. Unordered bulk writes keep going after a
+/// document fails, so failures are reported per entry instead of aborting the whole batch.
+pub struct Writer {
+    database: Database,
+    collection: Collection<Document>,
+    write_concern: Option<WriteConcern>,
+    mode: WriteMode,
+    batch_size: usize,
+    // buffered documents with their source entry ids
+    buffer: Vec<(u32, Document)>,
+}
+
+/// Result of one bulk write.
+#[derive(Debug, Default)]
+pub struct Flush {
+    // last entry id sent to the database, either written or failed
+    pub last_entry_id: Option<u32>,
+    pub written: u64,
+    pub failures: Vec<Failure>,
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub entry_id: u32,
+    pub message: String,
+}
+
+impl Writer {
+    pub(super) fn new(
+        database: Database,
+        collection: Collection<Document>,
+        write_concern: Option<WriteConcern>,
+        mode: WriteMode,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            database,
+            collection,
+            write_concern,
+            mode,
+            batch_size,
+            buffer: Vec::with_capacity(batch_size),
+        }
+    }
+
+    /// Buffer definition and return the flush result if the buffer was full.
+    pub async fn write(&mut self, definition: &Definition) -> Result<Option<Flush>> {
+        trace!(
+            "kb::writer::Writer::write(&mut self, definition: &Definition) -> Result<Option<Flush>>"
+        );
+        self.buffer
+            .push((definition.entry_id(), to_document(definition)?));
+        if self.buffer.len() < self.batch_size {
+            return Ok(None);
+        }
+        Ok(Some(self.flush().await?))
+    }
+
+    /// Send buffered definitions; failed documents are reported, not retried.
+    pub async fn flush(&mut self) -> Result<Flush> {
+        trace!("kb::writer::Writer::flush(&mut self) -> Result<Flush>");
+        let Some((last_entry_id, _)) = self.buffer.last() else {
+            return Ok(Flush::default());
+        };
+        let last_entry_id = Some(*last_entry_id);
+
+        let (entry_ids, documents): (Vec<u32>, Vec<Document>) = self.buffer.drain(..).unzip();
+        let (written, failures) = match self.mode {
+            WriteMode::Insert => self.insert(&entry_ids, documents).await?,
+            WriteMode::Upsert => self.upsert(&entry_ids, documents).await?,
+        };
+
+        for failure in &failures {
+            warn!(
+                "definition for entry {} not written: {}",
+                failure.entry_id, failure.message
+            );
+        }
+        debug!(
+            "bulk write of {written} definitions, {} failures",
+            failures.len()
+        );
+        Ok(Flush {
+            last_entry_id,
+            written,
+            failures,
+        })
+    }
+
+    async fn insert(
+        &self,
+        entry_ids: &[u32],
+        documents: Vec<Document>,
+    ) -> Result<(u64, Vec<Failure>)> {
+        let options = InsertManyOptions::builder()
+            .ordered(false)
+            .write_concern(self.write_concern.clone())
+            .build();
+        let failures = match self.collection.insert_many(documents, options).await {
+            Ok(_) => Vec::new(),
+            Err(error) => match error.kind.as_ref() {
+                ErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => failure
+                    .write_errors
+                    .iter()
+                    .flatten()
+                    .map(|write_error| Failure {
+                        entry_id: entry_ids[write_error.index],
+                        message: write_error.message.clone(),
+                    })
+                    .collect(),
+                _ => return Err(error.into()),
+            },
+        };
+        Ok(((entry_ids.len() - failures.len()) as u64, failures))
+    }
+
+    /// Replace by entry id with `update` commands carrying one upsert statement per document;
+    /// driver collection API would need one round trip per document. Unlike bulk inserts, raw
+    /// commands are not split by the driver, so statements are sent in chunks below the BSON
+    /// document size limit and the server write batch size.
+    async fn upsert(
+        &self,
+        entry_ids: &[u32],
+        documents: Vec<Document>,
+    ) -> Result<(u64, Vec<Failure>)> {
+        let mut failures = Vec::new();
+        let mut chunk: Vec<(u32, Document)> = Vec::new();
+        let mut chunk_size = 0;
+        for (entry_id, document) in entry_ids.iter().zip(documents) {
+            let update = doc! { "q": { "entry_id": *entry_id }, "u": document, "upsert": true };
+            let size = bson::to_vec(&update)?.len();
+            if !chunk.is_empty()
+                && (chunk_size + size > MAX_COMMAND_SIZE || chunk.len() == MAX_STATEMENTS)
+            {
+                failures.extend(self.update(std::mem::take(&mut chunk)).await?);
+                chunk_size = 0;
+            }
+            chunk.push((*entry_id, update));
+            chunk_size += size;
+        }
+        if !chunk.is_empty() {
+            failures.extend(self.update(chunk).await?);
+        }
+        Ok(((entry_ids.len() - failures.len()) as u64, failures))
+    }
+
+    async fn update(&self, updates: Vec<(u32, Document)>) -> Result<Vec<Failure>> {
+        let (entry_ids, updates): (Vec<u32>, Vec<Document>) = updates.into_iter().unzip();
+        let mut command = doc! {
+            "update": self.collection.name(),
+            "updates": updates,
+            "ordered": false,
+        };
+        if let Some(write_concern) = &self.write_concern {
+            command.insert("writeConcern", to_bson(write_concern)?);
+        }
+
+        let reply = self.database.run_command(command, None).await?;
+        if let Ok(error) = reply.get_document("writeConcernError") {
+            return Err(AppError::Write(format!("write concern error: {error}")));
+        }
+        Ok(match reply.get_array("writeErrors") {
+            Ok(errors) => errors
+                .iter()
+                .filter_map(Bson::as_document)
+                .map(|error| Failure {
+                    entry_id: error
+                        .get_i32("index")
+                        .ok()
+                        .and_then(|index| entry_ids.get(index as usize).copied())
+                        .unwrap_or_default(),
+                    message: error.get_str("errmsg").unwrap_or_default().to_string(),
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+}
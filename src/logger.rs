@@ -18,10 +18,10 @@ pub fn init(level: &str, file_path: &Option<String>) {
         )
     });
 
-    if let Some(file_path) = file_path {
-        if let Ok(file) = File::create(file_path) {
-            builder.target(Target::Pipe(Box::new(file)));
-        }
+    if let Some(file_path) = file_path
+        && let Ok(file) = File::create(file_path)
+    {
+        builder.target(Target::Pipe(Box::new(file)));
     }
 
     builder.init();
@@ -1,44 +1,106 @@
+pub(crate) mod schema;
+
 use crate::{
     error::{AppError, Result},
     util::strings,
 };
-use serde::Serialize;
+use deunicode::deunicode;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fmt;
+
+/// Key forms of an inflected form: lowercase and its ASCII folding, e.g. `casă` and `casa`.
+pub fn key_forms(form: &str) -> [String; 2] {
+    let form = form.to_lowercase();
+    let folded = deunicode(&form);
+    [form, folded]
+}
 
-#[derive(Serialize, Debug)]
+// fields with `#[serde(default)]`, here and in nested types, are missing from documents
+// imported before they were added
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Definition {
+    // source DEX entry id, unique in knowledge database
+    entry_id: u32,
     word: String,
     // key is a space separated string of all word's flexions, in both UTF-8 and ASCII formats
     key: String,
+    // same forms as key, one per element, for exact lookups
+    #[serde(default)]
+    forms: Vec<String>,
     part_of_speech: Option<String>,
     meanings: Vec<Meaning>,
     expressions: Vec<Expression>,
+    #[serde(default)]
+    etymologies: Vec<Etymology>,
+    // inflected forms, per lexeme
+    #[serde(default)]
+    paradigm: Vec<Paradigm>,
 }
 
 impl Definition {
     pub fn builder() -> DefinitionBuilder {
         DefinitionBuilder::new()
     }
+
+    pub fn entry_id(&self) -> u32 {
+        self.entry_id
+    }
+
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    pub fn part_of_speech(&self) -> Option<&str> {
+        self.part_of_speech.as_deref()
+    }
+
+    pub fn meanings(&self) -> &[Meaning] {
+        &self.meanings
+    }
+
+    pub fn expressions(&self) -> &[Expression] {
+        &self.expressions
+    }
+
+    pub fn etymologies(&self) -> &[Etymology] {
+        &self.etymologies
+    }
+
+    pub fn paradigm(&self) -> &[Paradigm] {
+        &self.paradigm
+    }
 }
 pub struct DefinitionBuilder {
+    entry_id: Option<u32>,
     word: Option<String>,
     keys: HashSet<String>,
     part_of_speech: Option<String>,
     meanings: Vec<Meaning>,
     expressions: Vec<Expression>,
+    etymologies: Vec<Etymology>,
+    paradigm: Vec<Paradigm>,
 }
 
 impl DefinitionBuilder {
     fn new() -> Self {
         DefinitionBuilder {
+            entry_id: None,
             word: None,
             keys: HashSet::new(),
             part_of_speech: None,
             meanings: Vec::new(),
             expressions: Vec::new(),
+            etymologies: Vec::new(),
+            paradigm: Vec::new(),
         }
     }
 
+    pub fn entry_id(mut self, entry_id: u32) -> Self {
+        self.entry_id = Some(entry_id);
+        self
+    }
+
     pub fn word(mut self, word: &str) -> Self {
         self.word = Some(word.to_string());
         self
@@ -64,21 +126,40 @@ impl DefinitionBuilder {
         self
     }
 
+    pub fn etymology(mut self, etymology: Etymology) -> Self {
+        self.etymologies.push(etymology);
+        self
+    }
+
+    pub fn paradigm(mut self, paradigm: Paradigm) -> Self {
+        self.paradigm.push(paradigm);
+        self
+    }
+
     pub fn build(mut self) -> Result<Definition> {
+        let entry_id = self
+            .entry_id
+            .ok_or(AppError::Fatal("definition entry id"))?;
         let word = self.word.ok_or(AppError::Fatal("definition word"))?;
         self.keys.insert(word.clone());
+        let mut forms: Vec<String> = self.keys.into_iter().collect();
+        forms.sort_unstable();
 
         Ok(Definition {
+            entry_id,
             word,
-            key: self.keys.into_iter().collect::<Vec<String>>().join(" "),
+            key: forms.join(" "),
+            forms,
             part_of_speech: self.part_of_speech,
             meanings: self.meanings,
             expressions: self.expressions,
+            etymologies: self.etymologies,
+            paradigm: self.paradigm,
         })
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Expression {
     phrase: String,
     definition: String,
@@ -106,9 +187,21 @@ impl Expression {
     pub fn add_example(&mut self, example: Example) {
         self.examples.push(example);
     }
+
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    pub fn examples(&self) -> &[Example] {
+        &self.examples
+    }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Example {
     text: String,
     source: Option<String>,
@@ -125,12 +218,32 @@ impl Example {
     pub fn set_source(&mut self, source: &str) {
         self.source = Some(source.to_string());
     }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
 }
 
-#[derive(Serialize, Debug)]
+// fields with `#[serde(default)]`: see `Definition`
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Meaning {
+    // position in meaning tree, e.g. `1.2` for the second sub-meaning of the first meaning
+    #[serde(default)]
+    breadcrumb: Option<String>,
     definition: String,
     examples: Vec<Example>,
+    #[serde(default)]
+    relations: Relations,
+    // editorial comments and diffs, only when imported with notes
+    #[serde(default)]
+    notes: Vec<Note>,
+    // sub-meanings
+    #[serde(default)]
+    meanings: Vec<Meaning>,
 }
 
 impl Meaning {
@@ -145,12 +258,345 @@ impl Meaning {
             definition = format!("{definition}.");
         }
         Self {
+            breadcrumb: None,
             definition,
             examples: Vec::new(),
+            relations: Relations::default(),
+            notes: Vec::new(),
+            meanings: Vec::new(),
         }
     }
 
+    pub fn set_breadcrumb(&mut self, breadcrumb: &str) {
+        self.breadcrumb = Some(breadcrumb.to_string());
+    }
+
     pub fn add_example(&mut self, example: Example) {
         self.examples.push(example);
     }
+
+    pub fn add_relation(&mut self, kind: RelationKind, tree_id: u32, word: &str) {
+        let relation = Relation {
+            tree_id,
+            word: word.to_string(),
+        };
+        match kind {
+            RelationKind::Synonym => self.relations.synonyms.push(relation),
+            RelationKind::Antonym => self.relations.antonyms.push(relation),
+            RelationKind::Diminutive => self.relations.diminutives.push(relation),
+            RelationKind::Augmentative => self.relations.augmentatives.push(relation),
+        }
+    }
+
+    pub fn add_note(&mut self, kind: NoteKind, text: &str) {
+        self.notes.push(Note {
+            kind,
+            text: text.to_string(),
+        });
+    }
+
+    pub fn add_meaning(&mut self, meaning: Meaning) {
+        self.meanings.push(meaning);
+    }
+
+    pub fn breadcrumb(&self) -> Option<&str> {
+        self.breadcrumb.as_deref()
+    }
+
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    pub fn examples(&self) -> &[Example] {
+        &self.examples
+    }
+
+    pub fn relations(&self) -> &Relations {
+        &self.relations
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    pub fn meanings(&self) -> &[Meaning] {
+        &self.meanings
+    }
+}
+
+/// Related words of a meaning, as thesaurus links to their DEX trees.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Relations {
+    synonyms: Vec<Relation>,
+    antonyms: Vec<Relation>,
+    diminutives: Vec<Relation>,
+    augmentatives: Vec<Relation>,
+}
+
+impl Relations {
+    /// Non empty relation lists, by kind.
+    pub fn by_kind(&self) -> impl Iterator<Item = (RelationKind, &[Relation])> {
+        [
+            (RelationKind::Synonym, self.synonyms.as_slice()),
+            (RelationKind::Antonym, self.antonyms.as_slice()),
+            (RelationKind::Diminutive, self.diminutives.as_slice()),
+            (RelationKind::Augmentative, self.augmentatives.as_slice()),
+        ]
+        .into_iter()
+        .filter(|(_, relations)| !relations.is_empty())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Relation {
+    tree_id: u32,
+    word: String,
+}
+
+impl Relation {
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+}
+
+/// DEX relation types.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelationKind {
+    Synonym,
+    Antonym,
+    Diminutive,
+    Augmentative,
+}
+
+impl RelationKind {
+    pub fn from_type(relation_type: u8) -> Option<Self> {
+        match relation_type {
+            1 => Some(RelationKind::Synonym),
+            2 => Some(RelationKind::Antonym),
+            3 => Some(RelationKind::Diminutive),
+            4 => Some(RelationKind::Augmentative),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RelationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelationKind::Synonym => write!(f, "synonyms"),
+            RelationKind::Antonym => write!(f, "antonyms"),
+            RelationKind::Diminutive => write!(f, "diminutives"),
+            RelationKind::Augmentative => write!(f, "augmentatives"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Note {
+    kind: NoteKind,
+    text: String,
+}
+
+impl Note {
+    pub fn kind(&self) -> NoteKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// DEX meaning type a note comes from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    Comment,
+    Diff,
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteKind::Comment => write!(f, "comment"),
+            NoteKind::Diff => write!(f, "diff"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Etymology {
+    text: String,
+    origins: Vec<Origin>,
+}
+
+impl Etymology {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            origins: Vec::new(),
+        }
+    }
+
+    pub fn add_origin(&mut self, language: Option<&str>, phrase: &str) {
+        self.origins.push(Origin {
+            language: language.map(str::to_string),
+            phrase: phrase.to_string(),
+        });
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn origins(&self) -> &[Origin] {
+        &self.origins
+    }
+}
+
+/// Word a definition originates from, with its language abbreviation, e.g. `fr` or `lat`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Origin {
+    language: Option<String>,
+    phrase: String,
+}
+
+impl Origin {
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+}
+
+/// Inflected forms of one lexeme, e.g. `casă` declined by model `F1`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Paradigm {
+    lexeme: String,
+    // inflection model code, type and number
+    model: String,
+    forms: Vec<InflectedForm>,
+}
+
+impl Paradigm {
+    pub fn new(lexeme: &str, model: &str) -> Self {
+        Self {
+            lexeme: lexeme.to_string(),
+            model: model.to_string(),
+            forms: Vec::new(),
+        }
+    }
+
+    pub fn add_form(&mut self, inflection: &str, form: &str) {
+        self.forms.push(InflectedForm {
+            inflection: inflection.to_string(),
+            form: form.to_string(),
+        });
+    }
+
+    pub fn lexeme(&self) -> &str {
+        &self.lexeme
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn forms(&self) -> &[InflectedForm] {
+        &self.forms
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InflectedForm {
+    // DEX inflection description, e.g. `Nominativ-Acuzativ, singular, nearticulat`
+    inflection: String,
+    form: String,
+}
+
+impl InflectedForm {
+    pub fn inflection(&self) -> &str {
+        &self.inflection
+    }
+
+    pub fn form(&self) -> &str {
+        &self.form
+    }
+}
+
+/// Definition with every field and nested type set, shared by tests.
+#[cfg(test)]
+impl Definition {
+    pub(crate) fn sample() -> Self {
+        let mut example = Example::new("Și-a luat casă.");
+        example.set_source("Autor");
+        let mut meaning = Meaning::new("clădire de locuit");
+        meaning.set_breadcrumb("1");
+        meaning.add_example(example);
+        meaning.add_relation(RelationKind::Synonym, 4294967295, "locuință");
+        meaning.add_relation(RelationKind::Synonym, 8, "clădire");
+        meaning.add_note(NoteKind::Comment, "Învechit.");
+        let mut sub_meaning = Meaning::new("locuință");
+        sub_meaning.set_breadcrumb("1.1");
+        meaning.add_meaning(sub_meaning);
+        let mut expression = Expression::new("Casă de bani.", "Seif.");
+        expression.add_example(Example::new("Banii stau în casa de bani."));
+        let mut etymology = Etymology::new("Din lat. casa.");
+        etymology.add_origin(Some("lat"), "casa");
+        let mut paradigm = Paradigm::new("casă", "F1");
+        paradigm.add_form("Nominativ-Acuzativ, singular, nearticulat", "casă");
+        Definition::builder()
+            .entry_id(4294967295)
+            .word("casă")
+            .key("case")
+            .part_of_speech("substantiv feminin")
+            .meaning(meaning)
+            // numbered by position
+            .meaning(Meaning::new("familie"))
+            .expression(expression)
+            .etymology(etymology)
+            .paradigm(paradigm)
+            .build()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{from_document, oid::ObjectId, to_document};
+
+    #[test]
+    fn test_round_trip() {
+        let definition = Definition::sample();
+
+        let mut document = to_document(&definition).unwrap();
+        assert_eq!(
+            from_document::<Definition>(document.clone()).unwrap(),
+            definition
+        );
+
+        // fields added by knowledge database are ignored
+        document.insert("deleted_at", "2024-01-01T00:00:00.000Z");
+        document.insert("_id", ObjectId::new());
+        assert_eq!(
+            from_document::<Definition>(document.clone()).unwrap(),
+            definition
+        );
+
+        // written before forms, etymologies, paradigms and meaning trees
+        document.remove("forms");
+        document.remove("etymologies");
+        document.remove("paradigm");
+        let meanings = document.get_array_mut("meanings").unwrap();
+        let meaning = meanings[0].as_document_mut().unwrap();
+        for field in ["breadcrumb", "relations", "notes", "meanings"] {
+            meaning.remove(field);
+        }
+        let legacy = from_document::<Definition>(document).unwrap();
+        assert!(legacy.etymologies().is_empty());
+        assert!(legacy.meanings()[0].meanings().is_empty());
+    }
 }
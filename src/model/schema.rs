@@ -0,0 +1,297 @@
+use crate::model::{
+    Definition, Etymology, Example, Expression, InflectedForm, Meaning, Note, Origin, Paradigm,
+    Relation, Relations,
+};
+use mongodb::bson::{Bson, Document, doc};
+use serde_json::{Map, Value};
+
+/// MongoDB `$jsonSchema` of a type stored in knowledge database; kept next to the model so
+/// that both change together.
+pub trait Schema {
+    fn schema() -> Document;
+}
+
+impl Schema for Definition {
+    fn schema() -> Document {
+        object_with_optional(
+            &[
+                ("entry_id", id()),
+                ("word", doc! { "bsonType": "string", "minLength": 1 }),
+                ("key", string()),
+                ("forms", array(string())),
+                ("part_of_speech", nullable_string()),
+                ("meanings", array(Meaning::schema())),
+                ("expressions", array(Expression::schema())),
+                ("etymologies", array(Etymology::schema())),
+                ("paradigm", array(Paradigm::schema())),
+            ],
+            &["forms", "etymologies", "paradigm"],
+        )
+    }
+}
+
+impl Schema for Meaning {
+    fn schema() -> Document {
+        meaning(MEANING_DEPTH)
+    }
+}
+
+impl Schema for Expression {
+    fn schema() -> Document {
+        object(&[
+            ("phrase", string()),
+            ("definition", string()),
+            ("examples", array(Example::schema())),
+        ])
+    }
+}
+
+impl Schema for Relations {
+    fn schema() -> Document {
+        object(&[
+            ("synonyms", array(Relation::schema())),
+            ("antonyms", array(Relation::schema())),
+            ("diminutives", array(Relation::schema())),
+            ("augmentatives", array(Relation::schema())),
+        ])
+    }
+}
+
+impl Schema for Relation {
+    fn schema() -> Document {
+        object(&[("tree_id", id()), ("word", string())])
+    }
+}
+
+impl Schema for Note {
+    fn schema() -> Document {
+        object(&[
+            (
+                "kind",
+                doc! { "bsonType": "string", "enum": ["comment", "diff"] },
+            ),
+            ("text", string()),
+        ])
+    }
+}
+
+impl Schema for Etymology {
+    fn schema() -> Document {
+        object(&[("text", string()), ("origins", array(Origin::schema()))])
+    }
+}
+
+impl Schema for Origin {
+    fn schema() -> Document {
+        object(&[("language", nullable_string()), ("phrase", string())])
+    }
+}
+
+impl Schema for Paradigm {
+    fn schema() -> Document {
+        object(&[
+            ("lexeme", string()),
+            ("model", string()),
+            ("forms", array(InflectedForm::schema())),
+        ])
+    }
+}
+
+impl Schema for InflectedForm {
+    fn schema() -> Document {
+        object(&[("inflection", string()), ("form", string())])
+    }
+}
+
+impl Schema for Example {
+    fn schema() -> Document {
+        object(&[("text", string()), ("source", nullable_string())])
+    }
+}
+
+// `$jsonSchema` has no references, so nested meanings are validated up to this depth; deeper
+// sub-meanings are only required to be objects
+const MEANING_DEPTH: usize = 4;
+
+fn meaning(depth: usize) -> Document {
+    let sub_meaning = match depth {
+        0 => doc! { "bsonType": "object" },
+        _ => meaning(depth - 1),
+    };
+    object_with_optional(
+        &[
+            ("breadcrumb", nullable_string()),
+            ("definition", string()),
+            ("examples", array(Example::schema())),
+            ("relations", Relations::schema()),
+            ("notes", array(Note::schema())),
+            ("meanings", array(sub_meaning)),
+        ],
+        &["breadcrumb", "relations", "notes", "meanings"],
+    )
+}
+
+/// Standard JSON Schema equivalent of a `$jsonSchema` document, for consumers outside MongoDB.
+pub fn to_json_schema(schema: &Document, title: &str) -> Value {
+    let mut json = Map::new();
+    json.insert(
+        "$schema".to_string(),
+        Value::from("https://json-schema.org/draft/2020-12/schema"),
+    );
+    json.insert("title".to_string(), Value::from(title));
+    if let Value::Object(converted) = convert(&Bson::Document(schema.clone())) {
+        json.extend(converted);
+    }
+    Value::Object(json)
+}
+
+fn convert(bson: &Bson) -> Value {
+    match bson {
+        Bson::Document(document) => Value::Object(
+            document
+                .iter()
+                .map(|(key, value)| match key.as_str() {
+                    "bsonType" => ("type".to_string(), json_type(value)),
+                    _ => (key.clone(), convert(value)),
+                })
+                .collect(),
+        ),
+        Bson::Array(values) => Value::Array(values.iter().map(convert).collect()),
+        other => other.clone().into_relaxed_extjson(),
+    }
+}
+
+fn json_type(bson_type: &Bson) -> Value {
+    let mut types: Vec<&str> = Vec::new();
+    let bson_types = match bson_type {
+        Bson::Array(values) => values.iter().filter_map(Bson::as_str).collect(),
+        other => other.as_str().into_iter().collect::<Vec<&str>>(),
+    };
+    for bson_type in bson_types {
+        let json_type = match bson_type {
+            "int" | "long" => "integer",
+            "double" | "decimal" => "number",
+            "bool" => "boolean",
+            // as exported in extended JSON, e.g. {"$oid": ...}
+            "objectId" | "date" => "object",
+            other => other,
+        };
+        if !types.contains(&json_type) {
+            types.push(json_type);
+        }
+    }
+    match types.as_slice() {
+        [single] => Value::from(*single),
+        _ => Value::from(types),
+    }
+}
+
+// every field is serialized, missing options as null
+fn object(fields: &[(&str, Document)]) -> Document {
+    object_with_optional(fields, &[])
+}
+
+// optional fields were added to the model later, documents written before may not have them
+fn object_with_optional(fields: &[(&str, Document)], optional: &[&str]) -> Document {
+    let mut properties = Document::new();
+    for (name, schema) in fields {
+        properties.insert(*name, schema.clone());
+    }
+    let required: Vec<&str> = fields
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| !optional.contains(name))
+        .collect();
+    doc! {
+        "bsonType": "object",
+        "required": required,
+        "properties": properties,
+        "additionalProperties": false,
+    }
+}
+
+fn array(items: Document) -> Document {
+    doc! { "bsonType": "array", "items": items }
+}
+
+// DEX ids are u32, serialized as long
+fn id() -> Document {
+    doc! { "bsonType": ["int", "long"], "minimum": 0, "maximum": i64::from(u32::MAX) }
+}
+
+fn string() -> Document {
+    doc! { "bsonType": "string" }
+}
+
+fn nullable_string() -> Document {
+    doc! { "bsonType": ["string", "null"] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::to_document;
+
+    fn assert_keys<T: Schema>(document: &Document) {
+        let schema = T::schema();
+        let declared: Vec<&String> = schema.get_document("properties").unwrap().keys().collect();
+        assert_eq!(document.keys().collect::<Vec<&String>>(), declared);
+    }
+
+    fn first<'a>(document: &'a Document, field: &str) -> &'a Document {
+        document.get_array(field).unwrap()[0].as_document().unwrap()
+    }
+
+    #[test]
+    fn test_schema_covers_serialized_fields() {
+        let definition = Definition::sample();
+
+        let document = to_document(&definition).unwrap();
+        assert_keys::<Definition>(&document);
+        let meaning = first(&document, "meanings");
+        assert_keys::<Meaning>(meaning);
+        assert_keys::<Meaning>(first(meaning, "meanings"));
+        assert_keys::<Example>(first(meaning, "examples"));
+        let relations = meaning.get_document("relations").unwrap();
+        assert_keys::<Relations>(relations);
+        assert_keys::<Relation>(first(relations, "synonyms"));
+        assert_keys::<Note>(first(meaning, "notes"));
+        let expression = first(&document, "expressions");
+        assert_keys::<Expression>(expression);
+        assert_keys::<Example>(first(expression, "examples"));
+        let etymology = first(&document, "etymologies");
+        assert_keys::<Etymology>(etymology);
+        assert_keys::<Origin>(first(etymology, "origins"));
+        let paradigm = first(&document, "paradigm");
+        assert_keys::<Paradigm>(paradigm);
+        assert_keys::<InflectedForm>(first(paradigm, "forms"));
+
+        let sub_meaning_schema = Meaning::schema()
+            .get_document("properties")
+            .unwrap()
+            .get_document("meanings")
+            .unwrap()
+            .get_document("items")
+            .unwrap()
+            .clone();
+        assert_eq!(sub_meaning_schema, super::meaning(MEANING_DEPTH - 1));
+    }
+
+    #[test]
+    fn test_to_json_schema() {
+        let json = to_json_schema(&Example::schema(), "Example");
+        assert_eq!(json["title"], "Example");
+        assert_eq!(json["type"], "object");
+        assert_eq!(
+            json["properties"]["source"]["type"],
+            serde_json::json!(["string", "null"])
+        );
+
+        let json = to_json_schema(&Definition::schema(), "Definition");
+        assert_eq!(json["properties"]["entry_id"]["type"], "integer");
+        assert_eq!(json["properties"]["entry_id"]["maximum"], 4294967295_i64);
+        let required = json["required"].as_array().unwrap();
+        assert!(required.contains(&Value::from("meanings")));
+        assert!(!required.contains(&Value::from("paradigm")));
+    }
+}
@@ -0,0 +1,58 @@
+use log::{error, warn};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Shutdown request raised by SIGINT or SIGTERM.
+///
+/// The first signal only sets the request flag so that long running commands can stop at a safe
+/// point; a second signal terminates the process immediately.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    /// Start listening for termination signals; must be called from within tokio runtime.
+    pub fn listen() -> Self {
+        let requested = Arc::new(AtomicBool::new(false));
+        let shutdown = Self {
+            requested: requested.clone(),
+        };
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(error) = signal().await {
+                    error!("fail to listen for termination signals: {error}");
+                    return;
+                }
+                if requested.swap(true, Ordering::SeqCst) {
+                    warn!("second termination signal, exit immediately");
+                    std::process::exit(130);
+                }
+                warn!("termination signal, stop after current entry; repeat to force exit");
+            }
+        });
+
+        shutdown
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(unix)]
+async fn signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
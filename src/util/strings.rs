@@ -31,13 +31,7 @@ pub fn starts_with_uppercase(s: &str) -> bool {
 }
 
 pub fn ends_with_punctuation(s: &str) -> bool {
-    match s.chars().last() {
-        Some(first_char) => match first_char {
-            '.' | '!' | '?' => true,
-            _ => false,
-        },
-        None => false,
-    }
+    matches!(s.chars().last(), Some('.' | '!' | '?'))
 }
 
 pub fn _remove_last_char(s: &str) -> String {
@@ -146,56 +140,56 @@ mod tests {
 
     #[test]
     fn test_empty_string_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation(""), false);
+        assert!(!ends_with_punctuation(""));
     }
 
     #[test]
     fn test_ends_with_period_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello."), true);
+        assert!(ends_with_punctuation("hello."));
     }
 
     #[test]
     fn test_ends_with_comma_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello,"), false);
+        assert!(!ends_with_punctuation("hello,"));
     }
     #[test]
     fn test_ends_with_parenthesis_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("(hello)"), false);
+        assert!(!ends_with_punctuation("(hello)"));
     }
 
     #[test]
     fn test_ends_with_question_mark_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello?"), true);
+        assert!(ends_with_punctuation("hello?"));
     }
 
     #[test]
     fn test_ends_with_exclamation_mark_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello!"), true);
+        assert!(ends_with_punctuation("hello!"));
     }
 
     #[test]
     fn test_ends_with_letter_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello"), false);
+        assert!(!ends_with_punctuation("hello"));
     }
 
     #[test]
     fn test_ends_with_number_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello123"), false);
+        assert!(!ends_with_punctuation("hello123"));
     }
 
     #[test]
     fn test_ends_with_space_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello "), false);
+        assert!(!ends_with_punctuation("hello "));
     }
 
     #[test]
     fn test_ends_with_multiple_punctuation_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello!!"), true);
+        assert!(ends_with_punctuation("hello!!"));
     }
 
     #[test]
     fn test_ends_with_mixed_punctuation_on_ends_with_punctuation() {
-        assert_eq!(ends_with_punctuation("hello.1"), false);
+        assert!(!ends_with_punctuation("hello.1"));
     }
 
     // trim_end_punctuation
@@ -3,7 +3,7 @@ pub fn current_thread_id() -> u64 {
     use std::sync::Mutex;
 
     thread_local! {
-        static THREAD_ID: Cell<Option<u64>> = Cell::new(None);
+        static THREAD_ID: Cell<Option<u64>> = const { Cell::new(None) };
     }
 
     static NEXT_ID: Mutex<u64> = Mutex::new(0);
@@ -72,7 +72,7 @@ impl DateTime {
         let dst_start_day = Self::last_sunday_of_month(year, 3);
         let dst_end_day = Self::last_sunday_of_month(year, 10);
 
-        if month < 3 || month > 10 {
+        if !(3..=10).contains(&month) {
             false
         } else if month > 3 && month < 10 {
             true
@@ -113,7 +113,7 @@ impl DateTime {
     }
 
     fn date_components(days: u32) -> (u32, u32, u32) {
-        let mut year = 1970 as u32;
+        let mut year = 1970_u32;
         let mut remaining_days = days;
 
         loop {
@@ -154,7 +154,7 @@ impl DateTime {
     }
 
     fn is_leap_year(year: u32) -> bool {
-        (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
     }
 }
 
@@ -177,7 +177,7 @@ mod test {
     #[test]
     fn zero_utc_to_local_millis() {
         let millis = DateTime::local(0);
-        assert_eq!(millis, 7200_000);
+        assert_eq!(millis, 7_200_000);
     }
 
     #[test]
@@ -188,7 +188,7 @@ mod test {
 
     #[test]
     fn date_time_to_iso8601() {
-        let date_time = DateTime::from(226359000_000);
+        let date_time = DateTime::from(226_359_000_000);
         assert_eq!(date_time.iso8601(), "1977-03-04T21:30:00.000Z");
     }
 }